@@ -2,6 +2,7 @@ use std::path::PathBuf;
 
 use crate::errors::ClientError;
 
+#[allow(dead_code)]
 pub struct Config {
     pub host: String,
     pub workdir: PathBuf,
@@ -16,7 +16,7 @@ fn main() -> io::Result<()> {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Error: {}", e);
-            return Err(io::Error::new(io::ErrorKind::Other, e.to_string()));
+            return Err(io::Error::other(e.to_string()));
         }
     };
     println!(r" ______ _________ ______  _________ _____    ______  ");
@@ -1,12 +1,13 @@
 use std::io::{self, BufRead, BufReader, Write};
 use std::net::TcpStream;
 
-struct Client {
+/// Sends commands to a server, one connection per command.
+pub struct Client {
     host: String,
 }
 
 impl Client {
-    fn new(host: &str, port: usize) -> Self {
+    pub fn new(host: &str, port: usize) -> Self {
         Client {
             host: format!("{}:{}", host, port),
         }
@@ -49,4 +50,3 @@ impl Client {
         self.send_command(&command)
     }
 }
-
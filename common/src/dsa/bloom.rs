@@ -0,0 +1,369 @@
+//! This module provides a Bloom filter - structure that tells whether an item was maybe added
+//! or certainly not, in a fraction of the memory of the items themselves.
+//! Example:
+//! ```
+//! use common::dsa::bloom::BloomFilter;
+//!
+//! let mut filter = BloomFilter::new("filter_name".to_string(), 1000, 0.01).unwrap();
+//! filter.add("somekey");
+//!
+//! assert!(filter.contains("somekey"));
+//! assert!(!filter.contains("otherkey"));
+//! ```
+//!
+//! The filter is scalable: when it holds as many items as its capacity, a new filter with twice
+//! the capacity and half the error rate is stacked on top of it, so the overall error rate stays
+//! below the requested one. Items are hashed with FNV-1a, which is stable across Rust versions,
+//! so a serialized filter stays valid.
+
+use std::fmt;
+
+use log::info;
+
+use super::hash::{fnv1a, splitmix64};
+
+/// Upper bound for the bits of one filter, 512 MiB.
+pub const MAX_FILTER_BITS: u64 = 1 << 32;
+
+/// Each stacked filter has this fraction of the error rate of the previous one.
+const TIGHTENING_RATIO: f64 = 0.5;
+const GROWTH_FACTOR: usize = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum BloomError {
+    InvalidCapacity,
+    InvalidErrorRate,
+    TooLarge,
+    /// A deserialized filter is inconsistent.
+    InvalidSnapshot(&'static str),
+}
+
+impl fmt::Display for BloomError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BloomError::InvalidCapacity => write!(f, "capacity must be positive"),
+            BloomError::InvalidErrorRate => write!(f, "error rate must be between 0 and 1"),
+            BloomError::TooLarge => write!(f, "filter would exceed {} bits", MAX_FILTER_BITS),
+            BloomError::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "LayerRepr")
+)]
+struct Layer {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    count: usize,
+}
+
+impl Layer {
+    /// Sizes the layer for capacity items at the error rate: m = -n ln(p) / ln(2)^2 bits
+    /// and k = m / n ln(2) hashes.
+    fn new(capacity: usize, error_rate: f64) -> Result<Self, BloomError> {
+        let ln2 = std::f64::consts::LN_2;
+        let num_bits = (-(capacity as f64) * error_rate.ln() / (ln2 * ln2)).ceil();
+        if num_bits > MAX_FILTER_BITS as f64 {
+            return Err(BloomError::TooLarge);
+        }
+        let num_bits = (num_bits as u64).max(64);
+        let num_hashes = ((num_bits as f64 / capacity as f64) * ln2).round().max(1.0) as u32;
+        Ok(Layer {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+            capacity,
+            count: 0,
+        })
+    }
+
+    /// Bit indexes of the item, by double hashing with a second hash mixed from the first.
+    fn indexes(&self, hash: u64) -> impl Iterator<Item = u64> + '_ {
+        let second = splitmix64(hash) | 1;
+        (0..u64::from(self.num_hashes))
+            .map(move |i| hash.wrapping_add(i.wrapping_mul(second)) % self.num_bits)
+    }
+
+    fn contains(&self, hash: u64) -> bool {
+        self.indexes(hash)
+            .all(|index| self.bits[(index / 64) as usize] & (1 << (index % 64)) != 0)
+    }
+
+    fn insert(&mut self, hash: u64) {
+        let indexes: Vec<u64> = self.indexes(hash).collect();
+        for index in indexes {
+            self.bits[(index / 64) as usize] |= 1 << (index % 64);
+        }
+        self.count += 1;
+    }
+}
+
+/// Summary of a [`BloomFilter`], returned by `info`.
+#[derive(Debug, PartialEq)]
+pub struct BloomInfo {
+    pub capacity: usize,
+    pub error_rate: f64,
+    pub items: usize,
+    pub filters: usize,
+    /// Memory used by the bit arrays, in bytes.
+    pub size: usize,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "BloomFilterRepr")
+)]
+pub struct BloomFilter {
+    pub name: String,
+    capacity: usize,
+    error_rate: f64,
+    layers: Vec<Layer>,
+}
+
+/// Unchecked serialized form of a [`Layer`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LayerRepr {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    capacity: usize,
+    count: usize,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<LayerRepr> for Layer {
+    type Error = BloomError;
+
+    fn try_from(repr: LayerRepr) -> Result<Self, BloomError> {
+        if repr.num_bits == 0 || repr.num_bits > MAX_FILTER_BITS {
+            return Err(BloomError::InvalidSnapshot("bit count out of range"));
+        }
+        if repr.bits.len() as u64 != repr.num_bits.div_ceil(64) {
+            return Err(BloomError::InvalidSnapshot(
+                "bits don't match the bit count",
+            ));
+        }
+        if repr.num_hashes == 0 || repr.capacity == 0 {
+            return Err(BloomError::InvalidSnapshot(
+                "layer without hashes or capacity",
+            ));
+        }
+        Ok(Layer {
+            bits: repr.bits,
+            num_bits: repr.num_bits,
+            num_hashes: repr.num_hashes,
+            capacity: repr.capacity,
+            count: repr.count,
+        })
+    }
+}
+
+/// Unchecked serialized form of a [`BloomFilter`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct BloomFilterRepr {
+    name: String,
+    capacity: usize,
+    error_rate: f64,
+    layers: Vec<Layer>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<BloomFilterRepr> for BloomFilter {
+    type Error = BloomError;
+
+    fn try_from(repr: BloomFilterRepr) -> Result<Self, BloomError> {
+        if repr.capacity == 0 {
+            return Err(BloomError::InvalidCapacity);
+        }
+        if !(repr.error_rate > 0.0 && repr.error_rate < 1.0) {
+            return Err(BloomError::InvalidErrorRate);
+        }
+        if repr.layers.is_empty() {
+            return Err(BloomError::InvalidSnapshot("filter without layers"));
+        }
+        Ok(BloomFilter {
+            name: repr.name,
+            capacity: repr.capacity,
+            error_rate: repr.error_rate,
+            layers: repr.layers,
+        })
+    }
+}
+
+impl BloomFilter {
+    /// Creates a filter sized to hold capacity items with the given false positive rate.
+    pub fn new(name: String, capacity: usize, error_rate: f64) -> Result<Self, BloomError> {
+        if capacity == 0 {
+            return Err(BloomError::InvalidCapacity);
+        }
+        if !(error_rate > 0.0 && error_rate < 1.0) {
+            return Err(BloomError::InvalidErrorRate);
+        }
+        info!(
+            "Creating new bloom filter with name: {}, capacity: {}, error rate: {}",
+            name, capacity, error_rate
+        );
+        // The rates of the stacked filters add up to at most the requested one.
+        let first = Layer::new(capacity, error_rate * (1.0 - TIGHTENING_RATIO))?;
+        Ok(BloomFilter {
+            name,
+            capacity,
+            error_rate,
+            layers: vec![first],
+        })
+    }
+
+    /// Adds the item. Returns false if it was maybe added before.
+    pub fn add(&mut self, item: &str) -> bool {
+        let hash = fnv1a(item);
+        if self.layers.iter().any(|layer| layer.contains(hash)) {
+            return false;
+        }
+        let last = self.layers.last().expect("a filter has at least one layer");
+        if last.count >= last.capacity {
+            let capacity = last.capacity.saturating_mul(GROWTH_FACTOR);
+            let error_rate = self.error_rate
+                * (1.0 - TIGHTENING_RATIO)
+                * TIGHTENING_RATIO.powi(self.layers.len() as i32);
+            match Layer::new(capacity, error_rate) {
+                Ok(layer) => {
+                    info!(
+                        "Stacking filter {} on bloom {}",
+                        self.layers.len(),
+                        self.name
+                    );
+                    self.layers.push(layer);
+                }
+                // Keep filling the last layer rather than fail, at a higher error rate.
+                Err(_) => info!("Bloom {} can't grow anymore", self.name),
+            }
+        }
+        self.layers
+            .last_mut()
+            .expect("a filter has at least one layer")
+            .insert(hash);
+        true
+    }
+
+    /// Returns false if the item was certainly not added.
+    pub fn contains(&self, item: &str) -> bool {
+        let hash = fnv1a(item);
+        self.layers.iter().any(|layer| layer.contains(hash))
+    }
+
+    pub fn add_many(&mut self, items: &[&str]) -> Vec<bool> {
+        items.iter().map(|item| self.add(item)).collect()
+    }
+
+    pub fn contains_many(&self, items: &[&str]) -> Vec<bool> {
+        items.iter().map(|item| self.contains(item)).collect()
+    }
+
+    pub fn info(&self) -> BloomInfo {
+        BloomInfo {
+            capacity: self.capacity,
+            error_rate: self.error_rate,
+            items: self.layers.iter().map(|layer| layer.count).sum(),
+            filters: self.layers.len(),
+            size: self.layers.iter().map(|layer| layer.bits.len() * 8).sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameters() {
+        assert_eq!(
+            BloomFilter::new("test".to_string(), 0, 0.01).unwrap_err(),
+            BloomError::InvalidCapacity
+        );
+        assert_eq!(
+            BloomFilter::new("test".to_string(), 10, 1.0).unwrap_err(),
+            BloomError::InvalidErrorRate
+        );
+        assert_eq!(
+            BloomFilter::new("test".to_string(), usize::MAX, 0.01).unwrap_err(),
+            BloomError::TooLarge
+        );
+
+        let filter = BloomFilter::new("test".to_string(), 1000, 0.01).unwrap();
+        let layer = &filter.layers[0];
+        // 0.5% per item for the first layer: about 11 bits and 8 hashes per item.
+        assert_eq!(layer.num_bits, 11028);
+        assert_eq!(layer.num_hashes, 8);
+    }
+
+    #[test]
+    fn test_add_and_contains() {
+        let mut filter = BloomFilter::new("test".to_string(), 100, 0.01).unwrap();
+        assert!(filter.add("a"));
+        assert!(!filter.add("a"));
+        assert_eq!(filter.add_many(&["a", "b"]), vec![false, true]);
+        assert_eq!(
+            filter.contains_many(&["a", "b", "c"]),
+            vec![true, true, false]
+        );
+        assert_eq!(filter.info().items, 2);
+    }
+
+    #[test]
+    fn test_scaling_keeps_error_rate() {
+        let mut filter = BloomFilter::new("test".to_string(), 1000, 0.01).unwrap();
+        for i in 0..10_000 {
+            filter.add(&format!("key{}", i));
+        }
+        assert!((0..10_000).all(|i| filter.contains(&format!("key{}", i))));
+        let info = filter.info();
+        assert_eq!(info.filters, 4);
+        assert!(info.items > 9_900);
+
+        let false_positives = (0..10_000)
+            .filter(|i| filter.contains(&format!("other{}", i)))
+            .count();
+        assert!(false_positives < 100, "{} false positives", false_positives);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_snapshot() {
+        let mut filter = BloomFilter::new("test".to_string(), 10, 0.01).unwrap();
+        for i in 0..30 {
+            filter.add(&format!("key{}", i));
+        }
+        let json = serde_json::to_string(&filter).unwrap();
+        let restored: BloomFilter = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.info(), filter.info());
+        assert!((0..30).all(|i| restored.contains(&format!("key{}", i))));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_invalid_snapshot() {
+        let filter = BloomFilter::new("test".to_string(), 10, 0.01).unwrap();
+        let json = serde_json::to_value(&filter).unwrap();
+        let corrupt = |path: &str, value: serde_json::Value| {
+            let mut json = json.clone();
+            *json.pointer_mut(path).unwrap() = value;
+            serde_json::from_value::<BloomFilter>(json)
+        };
+        assert!(corrupt("/layers/0/num_bits", 0.into()).is_err());
+        assert!(corrupt("/layers/0/num_bits", 1_000_000.into()).is_err());
+        assert!(corrupt("/layers/0/num_hashes", 0.into()).is_err());
+        assert!(corrupt("/layers", serde_json::json!([])).is_err());
+        assert!(corrupt("/error_rate", 2.0.into()).is_err());
+        assert!(corrupt("/name", "other".into()).is_ok());
+    }
+}
@@ -14,32 +14,164 @@
 //! assert_eq!(tree.get("mypath"), None);
 //! ```
 //!
+//! Nodes are shared between copies of a tree and copied only when they are modified,
+//! so `snapshot()` is cheap and a snapshot never changes, whatever happens to the tree later:
+//! ```
+//! use common::dsa::char_tree::CharTree;
+//!
+//! let mut tree = CharTree::new("tree_name".to_string());
+//! tree.insert("mypath", "somevalue");
+//! let snapshot = tree.snapshot();
+//! tree.insert("mypath", "othervalue");
+//!
+//! assert_eq!(snapshot.get("mypath").unwrap(), "somevalue");
+//! assert_eq!(tree.get("mypath").unwrap(), "othervalue");
+//! ```
+//!
+//! With the `serde` feature enabled, the tree implements `Serialize` and `Deserialize`.
+//!
+
+mod counter;
+mod glob;
+mod history;
+mod merge;
+mod regex;
+#[cfg(feature = "serde")]
+mod serialize;
+mod strings;
+#[cfg(feature = "serde")]
+pub use serialize::structural;
+
+pub use counter::CounterError;
+pub use glob::PatternError;
+pub use history::{Version, VersionPoint, DELETED_KEYS_LIMIT};
+pub use merge::{MergePolicy, TreeDiff};
+pub use regex::{RegexError, REGEX_SIZE_LIMIT};
+
+#[cfg(test)]
+mod proptests;
+
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, VecDeque};
+use std::sync::Arc;
 
 use log::{info, warn};
 
-#[derive(Debug)]
+/// Children are shared between trees and cloned with `Arc::make_mut` only when modified.
+type Children = Vec<Arc<Node>>;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 struct Node {
     name: char,
     value: Option<String>,
-    children: Vec<Box<Node>>,
+    children: Children,
+    /// Score of the key ending at this node, used to rank completions.
+    #[cfg_attr(feature = "serde", serde(default))]
+    score: u64,
+    /// Highest score of any key in this subtree, including this node.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    max_score: u64,
+    /// Past versions of the key, only kept by versioned trees.
+    #[cfg_attr(feature = "serde", serde(default))]
+    history: VecDeque<Version>,
 }
 
 impl Node {
     fn new(name: char) -> Self {
         info!("Creating new node with name: {}", name);
         Node {
-            name: name,
+            name,
             value: None,
             children: Vec::new(),
+            score: 0,
+            max_score: 0,
+            history: VecDeque::new(),
         }
     }
 
-    fn get_child_ref(&self, name: char) -> Option<&Box<Node>> {
-        self.children.iter().find(|node| node.name == name)
+    /// A node without value, children and history is not needed anymore.
+    fn is_empty(&self) -> bool {
+        self.value.is_none() && self.children.is_empty() && self.history.is_empty()
+    }
+
+    fn update_max_score(&mut self) {
+        let own = if self.value.is_some() { self.score } else { 0 };
+        self.max_score = self
+            .children
+            .iter()
+            .map(|child| child.max_score)
+            .fold(own, u64::max);
+    }
+
+    /// Number of values stored in this subtree, including the node itself.
+    fn count(&self) -> usize {
+        let own = usize::from(self.value.is_some());
+        own + self
+            .children
+            .iter()
+            .map(|child| child.count())
+            .sum::<usize>()
+    }
+
+    /// Collects all nodes holding a value in this subtree, with their keys.
+    /// The path must not include the node itself.
+    fn collect_nodes<'a>(&'a self, mut path: String, nodes: &mut Vec<(String, &'a Node)>) {
+        path.push(self.name);
+        if self.value.is_some() {
+            nodes.push((path.clone(), self));
+        }
+        for child in self.children.iter() {
+            child.collect_nodes(path.clone(), nodes);
+        }
+    }
+
+    fn get_child_ref(&self, name: char) -> Option<&Node> {
+        self.children
+            .iter()
+            .find(|node| node.name == name)
+            .map(|node| node.as_ref())
+    }
+
+    fn get_child_mut(&mut self, name: char) -> Option<&mut Node> {
+        self.children
+            .iter_mut()
+            .find(|node| node.name == name)
+            .map(Arc::make_mut)
+    }
+}
+
+/// A completion candidate: either a whole subtree, ranked by its best score,
+/// or a single key. Higher scores come first, ties are broken by key.
+struct Candidate<'a> {
+    score: u64,
+    path: String,
+    node: Option<&'a Node>,
+}
+
+impl Candidate<'_> {
+    fn rank(&self) -> (u64, Reverse<&String>, bool) {
+        (self.score, Reverse(&self.path), self.node.is_none())
+    }
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.rank() == other.rank()
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
+}
 
-    fn get_child_mut(&mut self, name: char) -> Option<&mut Box<Node>> {
-        self.children.iter_mut().find(|node| node.name == name)
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.rank().cmp(&other.rank())
     }
 }
 
@@ -49,18 +181,33 @@ impl Node {
 #[derive(Debug)]
 pub struct CharTree {
     pub name: String,
-    root: Vec<Box<Node>>,
+    root: Arc<Children>,
+    versioning: Option<history::Versioning>,
 }
 
 impl CharTree {
     pub fn new(name: String) -> Self {
         info!("Creating new tree with name: {}", name);
         CharTree {
-            root: Vec::new(),
-            name: name,
+            root: Arc::new(Vec::new()),
+            name,
+            versioning: None,
+        }
+    }
+
+    /// Returns a frozen copy of the tree in O(1). Nodes are shared until either tree modifies them.
+    pub fn snapshot(&self) -> CharTree {
+        CharTree {
+            name: self.name.clone(),
+            root: Arc::clone(&self.root),
+            versioning: self.versioning.clone(),
         }
     }
 
+    fn root_mut(&mut self) -> &mut Children {
+        Arc::make_mut(&mut self.root)
+    }
+
     fn consume_path(path: &mut &str) -> char {
         let first_char = path.chars().next().unwrap(); // Get the first character
         let next_char_index = path
@@ -79,24 +226,38 @@ impl CharTree {
             return;
         }
         info!("Inserting value: {} to path: {}", value, path);
+        let key = path;
         let first_char = Self::consume_path(&mut path);
-        if self.root.is_empty() {
-            let new_node = Box::new(Node::new(first_char));
-            self.root.push(new_node);
-            Self::insert_recursive(path, value, self.root.iter_mut().last().unwrap());
-            return;
+        let root = self.root_mut();
+        if let Some(current_node) = root.iter_mut().find(|n| n.name == first_char) {
+            Self::insert_recursive(path, value, Arc::make_mut(current_node));
+        } else {
+            let new_node = Arc::new(Node::new(first_char));
+            root.push(new_node);
+            Self::insert_recursive(path, value, Arc::make_mut(root.last_mut().unwrap()));
         }
+        self.record(key, Some(value));
+    }
 
-        if let Some(current_node) = self.root.iter_mut().find(|n| n.name == first_char) {
-            Self::insert_recursive(path, value, current_node);
-        } else {
-            let new_node = Box::new(Node::new(first_char));
-            self.root.push(new_node);
-            Self::insert_recursive(path, value, self.root.iter_mut().last().unwrap());
+    /// Inserts the value only if the key has none. Returns the existing value,
+    /// or None if the value was inserted.
+    pub fn insert_if_absent(&mut self, key: &str, value: &str) -> Option<String> {
+        let previous = self.get(key);
+        if previous.is_none() {
+            self.insert(key, value);
         }
+        previous
     }
 
-    fn insert_recursive(mut path: &str, value: &str, current_node: &mut Box<Node>) {
+    /// Replaces the value only if the key already has one. Returns the previous value,
+    /// or None if nothing was written.
+    pub fn replace(&mut self, key: &str, value: &str) -> Option<String> {
+        let previous = self.get(key)?;
+        self.insert(key, value);
+        Some(previous)
+    }
+
+    fn insert_recursive(mut path: &str, value: &str, current_node: &mut Node) {
         if path.is_empty() {
             current_node.value = Some(value.to_string());
             return;
@@ -105,8 +266,9 @@ impl CharTree {
         if let Some(child) = current_node.get_child_mut(first_char) {
             Self::insert_recursive(path, value, child)
         } else {
-            current_node.children.push(Box::new(Node::new(first_char)));
-            Self::insert_recursive(path, value, current_node.children.last_mut().unwrap())
+            current_node.children.push(Arc::new(Node::new(first_char)));
+            let child = current_node.children.last_mut().unwrap();
+            Self::insert_recursive(path, value, Arc::make_mut(child))
         }
     }
 
@@ -118,7 +280,7 @@ impl CharTree {
         }
         info!("Getting value from path: {}", path);
         let first_char = Self::consume_path(&mut path);
-        let mut current_node = self.root.iter().find(|&n| n.name == first_char)?;
+        let mut current_node: &Node = self.root.iter().find(|&n| n.name == first_char)?;
         while !path.is_empty() {
             let first_char = Self::consume_path(&mut path);
             if let Some(child) = current_node.get_child_ref(first_char) {
@@ -130,31 +292,46 @@ impl CharTree {
         current_node.value.clone()
     }
 
-    /// Like get(), but returns last value early if needed.
-    pub fn hit(&self, mut path: &str) -> Option<String> {
-        if self.root.is_empty() || path.is_empty() {
-            warn!("Empty path provided, skipping hit");
-            return None;
-        }
+    /// Like get(), but returns last value early if needed:
+    /// the result is the value of the longest stored key that is a prefix of the path.
+    pub fn hit(&self, path: &str) -> Option<String> {
         info!("Hitting value from path: {}", path);
-        let first_char = Self::consume_path(&mut path);
-        let mut current_node = self.root.iter().find(|&n| n.name == first_char)?;
-        let mut last_value = &current_node.value;
-        while !path.is_empty() {
-            let first_char = Self::consume_path(&mut path);
-            if let Some(child) = current_node.get_child_ref(first_char) {
-                if current_node.value.is_some() {
-                    last_value = &current_node.value;
-                }
-                current_node = child;
-            } else {
-                break;
+        let (_, value) = self.values_along(path).pop()?;
+        Some(value.clone())
+    }
+
+    /// Like hit(), but also returns the key that matched.
+    pub fn hit_with_key(&self, path: &str) -> Option<(String, String)> {
+        info!("Hitting key and value from path: {}", path);
+        let (len, value) = self.values_along(path).pop()?;
+        Some((path[..len].to_string(), value.clone()))
+    }
+
+    /// Returns every value stored along the path, from the shortest key to the longest.
+    pub fn hit_all(&self, path: &str) -> Vec<String> {
+        info!("Hitting all values from path: {}", path);
+        self.values_along(path)
+            .into_iter()
+            .map(|(_, value)| value.clone())
+            .collect()
+    }
+
+    /// Walks the path and collects every value on the way,
+    /// together with the byte length of the key it is stored at.
+    fn values_along<'a>(&'a self, path: &str) -> Vec<(usize, &'a String)> {
+        let mut result = Vec::new();
+        let mut nodes: &[Arc<Node>] = &self.root;
+        for (i, name) in path.char_indices() {
+            let node = match nodes.iter().find(|n| n.name == name) {
+                Some(node) => node,
+                None => break,
             };
+            if let Some(value) = &node.value {
+                result.push((i + name.len_utf8(), value));
+            }
+            nodes = &node.children;
         }
-        if current_node.value.is_some() {
-            return current_node.value.clone();
-        }
-        last_value.clone()
+        result
     }
 
     /// This a legacy shallow delete method, use deep_delete() instead.
@@ -164,9 +341,14 @@ impl CharTree {
             return;
         }
         info!("Shallow deleting value from path: {}", path);
+        let key = path;
+        if !self.has_value(key) {
+            return;
+        }
+        self.record_delete(key);
         let first_char = Self::consume_path(&mut path);
-        let mut current_node = match self.root.iter_mut().find(|n| n.name == first_char) {
-            Some(node) => node,
+        let mut current_node = match self.root_mut().iter_mut().find(|n| n.name == first_char) {
+            Some(node) => Arc::make_mut(node),
             None => {
                 return;
             }
@@ -181,6 +363,9 @@ impl CharTree {
             };
         }
         current_node.value = None;
+        current_node.score = 0;
+        // Scores above the deleted key may be stale now, so recompute them along the path.
+        Self::refresh_max_scores(self.root_mut(), key);
     }
 
     /// This is the main method for deletions. It deletes not just values, but not used nodes as well.
@@ -190,17 +375,30 @@ impl CharTree {
             return;
         }
         info!("Deep deleting value from path: {}", path);
+        // Empty nodes left by shallow deletes are deleted too.
+        if !self
+            .find_node(path)
+            .is_some_and(|node| node.value.is_some() || node.is_empty())
+        {
+            return;
+        }
+        self.record_delete(path);
         // Start deletion from the root nodes
         let first_char = Self::consume_path(&mut path);
-        if let Some(node) = self.root.iter_mut().find(|n| n.name == first_char) {
-            Self::deep_delete_recursive(node, path);
+        if let Some(pos) = self.root.iter().position(|n| n.name == first_char) {
+            let root = self.root_mut();
+            if Self::deep_delete_recursive(Arc::make_mut(&mut root[pos]), path) {
+                root.remove(pos);
+            }
         }
     }
 
-    fn deep_delete_recursive(node: &mut Box<Node>, mut path: &str) -> bool {
+    fn deep_delete_recursive(node: &mut Node, mut path: &str) -> bool {
         if path.is_empty() {
             node.value = None;
-            return node.children.is_empty();
+            node.score = 0;
+            node.update_max_score();
+            return node.is_empty();
         }
         let first_char = Self::consume_path(&mut path);
         if let Some(next) = node.get_child_mut(first_char) {
@@ -213,14 +411,384 @@ impl CharTree {
                     .unwrap();
                 node.children.remove(pos);
             }
+            node.update_max_score();
 
-            // If node has no value and no children, it can be deleted
-            return node.value.is_none() && node.children.is_empty();
+            // If node has no value, no children and no history, it can be deleted
+            return node.is_empty();
         }
 
         false // Node with the specified path was not found
     }
 
+    fn find_node(&self, path: &str) -> Option<&Node> {
+        let mut chars = path.chars();
+        let first_char = chars.next()?;
+        let mut node: &Node = self.root.iter().find(|n| n.name == first_char)?;
+        for name in chars {
+            node = node.get_child_ref(name)?;
+        }
+        Some(node)
+    }
+
+    /// Returns the node of the key for modification, copying the shared nodes on its path.
+    fn find_node_mut(&mut self, path: &str) -> Option<&mut Node> {
+        let mut chars = path.chars();
+        let first_char = chars.next()?;
+        let node = self.root_mut().iter_mut().find(|n| n.name == first_char)?;
+        let mut node = Arc::make_mut(node);
+        for name in chars {
+            node = node.get_child_mut(name)?;
+        }
+        Some(node)
+    }
+
+    /// Looks the key up without copying any shared node, which callers do before
+    /// modifying the path to it.
+    fn has_value(&self, path: &str) -> bool {
+        self.find_node(path)
+            .is_some_and(|node| node.value.is_some())
+    }
+
+    fn refresh_max_scores(nodes: &mut [Arc<Node>], path: &str) {
+        let mut chars = path.chars();
+        if let Some(name) = chars.next() {
+            if let Some(node) = nodes.iter_mut().find(|n| n.name == name) {
+                let node = Arc::make_mut(node);
+                Self::refresh_max_scores(&mut node.children, chars.as_str());
+                node.update_max_score();
+            }
+        }
+    }
+
+    fn update_score(
+        nodes: &mut [Arc<Node>],
+        path: &str,
+        update: impl FnOnce(u64) -> u64,
+    ) -> Option<u64> {
+        let mut chars = path.chars();
+        let name = chars.next()?;
+        let node = Arc::make_mut(nodes.iter_mut().find(|n| n.name == name)?);
+        let rest = chars.as_str();
+        let result = if rest.is_empty() {
+            node.value.as_ref()?;
+            node.score = update(node.score);
+            Some(node.score)
+        } else {
+            Self::update_score(&mut node.children, rest, update)
+        };
+        node.update_max_score();
+        result
+    }
+
+    /// Sets the score used to rank the key in complete(). Returns false if the key is not stored.
+    pub fn set_score(&mut self, path: &str, score: u64) -> bool {
+        info!("Setting score {} for path: {}", score, path);
+        if !self.has_value(path) {
+            return false;
+        }
+        Self::update_score(self.root_mut(), path, |_| score).is_some()
+    }
+
+    /// Adds to the score of the key, e.g. to count how often it was hit. Returns the new score.
+    pub fn add_score(&mut self, path: &str, delta: u64) -> Option<u64> {
+        info!("Adding score {} for path: {}", delta, path);
+        if !self.has_value(path) {
+            return None;
+        }
+        Self::update_score(self.root_mut(), path, |score| score.saturating_add(delta))
+    }
+
+    /// Returns up to k keys starting with the prefix, best scored first.
+    /// Subtrees are visited in order of their best score, so only the part
+    /// of the tree needed to produce the result is explored.
+    pub fn complete(&self, prefix: &str, k: usize) -> Vec<String> {
+        info!("Completing prefix: {}", prefix);
+        let mut heap = BinaryHeap::new();
+        if prefix.is_empty() {
+            for node in self.root.iter() {
+                heap.push(Candidate {
+                    score: node.max_score,
+                    path: node.name.to_string(),
+                    node: Some(node),
+                });
+            }
+        } else if let Some(node) = self.find_node(prefix) {
+            heap.push(Candidate {
+                score: node.max_score,
+                path: prefix.to_string(),
+                node: Some(node),
+            });
+        }
+
+        let mut result = Vec::new();
+        while result.len() < k {
+            let Some(candidate) = heap.pop() else {
+                break;
+            };
+            let Some(node) = candidate.node else {
+                result.push(candidate.path);
+                continue;
+            };
+            if node.value.is_some() {
+                heap.push(Candidate {
+                    score: node.score,
+                    path: candidate.path.clone(),
+                    node: None,
+                });
+            }
+            for child in node.children.iter() {
+                let mut path = candidate.path.clone();
+                path.push(child.name);
+                heap.push(Candidate {
+                    score: child.max_score,
+                    path,
+                    node: Some(child),
+                });
+            }
+        }
+        result
+    }
+
+    /// Returns keys within the given Levenshtein distance of the query, closest first.
+    /// Branches are abandoned as soon as no key below them can be close enough.
+    pub fn fuzzy(&self, query: &str, max_distance: usize) -> Vec<(String, usize)> {
+        info!("Fuzzy searching {} within distance {}", query, max_distance);
+        let query: Vec<char> = query.chars().collect();
+        let first_row: Vec<usize> = (0..=query.len()).collect();
+        let mut result = Vec::new();
+        for node in self.root.iter() {
+            Self::fuzzy_recursive(
+                node,
+                &query,
+                &first_row,
+                String::new(),
+                max_distance,
+                &mut result,
+            );
+        }
+        result.sort_by_key(|(_, distance)| *distance);
+        result
+    }
+
+    /// Computes the edit distance row for the node from its parent's row.
+    fn fuzzy_recursive(
+        node: &Node,
+        query: &[char],
+        previous_row: &[usize],
+        mut path: String,
+        max_distance: usize,
+        result: &mut Vec<(String, usize)>,
+    ) {
+        path.push(node.name);
+        let mut row = Vec::with_capacity(previous_row.len());
+        row.push(previous_row[0] + 1);
+        for (i, &name) in query.iter().enumerate() {
+            let substitution = previous_row[i] + usize::from(name != node.name);
+            let insertion = row[i] + 1;
+            let deletion = previous_row[i + 1] + 1;
+            row.push(substitution.min(insertion).min(deletion));
+        }
+
+        let distance = row[query.len()];
+        if node.value.is_some() && distance <= max_distance {
+            result.push((path.clone(), distance));
+        }
+        if row.iter().min().is_some_and(|&min| min <= max_distance) {
+            for child in node.children.iter() {
+                Self::fuzzy_recursive(child, query, &row, path.clone(), max_distance, result);
+            }
+        }
+    }
+
+    /// Removes the node at the path together with its whole subtree
+    /// and prunes ancestors that are left without values.
+    fn detach(nodes: &mut Children, path: &str) -> Option<Arc<Node>> {
+        let mut chars = path.chars();
+        let name = chars.next()?;
+        let rest = chars.as_str();
+        let pos = nodes.iter().position(|n| n.name == name)?;
+        if rest.is_empty() {
+            return Some(nodes.remove(pos));
+        }
+        let node = Arc::make_mut(&mut nodes[pos]);
+        let detached = Self::detach(&mut node.children, rest)?;
+        node.update_max_score();
+        if node.is_empty() {
+            nodes.remove(pos);
+        }
+        Some(detached)
+    }
+
+    /// Attaches the subtree at the path, creating missing nodes on the way.
+    /// Values of the subtree overwrite values already stored under the path.
+    fn graft(nodes: &mut Children, path: &str, mut subtree: Node) {
+        let mut chars = path.chars();
+        let Some(name) = chars.next() else {
+            return;
+        };
+        let rest = chars.as_str();
+        let pos = match nodes.iter().position(|n| n.name == name) {
+            Some(pos) => pos,
+            None => {
+                nodes.push(Arc::new(Node::new(name)));
+                nodes.len() - 1
+            }
+        };
+        let node = Arc::make_mut(&mut nodes[pos]);
+        if rest.is_empty() {
+            subtree.name = name;
+            Self::merge_node(node, subtree);
+        } else {
+            Self::graft(&mut node.children, rest, subtree);
+        }
+        node.update_max_score();
+    }
+
+    fn merge_node(into: &mut Node, from: Node) {
+        if from.value.is_some() {
+            into.value = from.value;
+            into.score = from.score;
+        }
+        for child in from.children {
+            match into.get_child_mut(child.name) {
+                Some(existing) => Self::merge_node(existing, Arc::unwrap_or_clone(child)),
+                None => into.children.push(child),
+            }
+        }
+        into.update_max_score();
+    }
+
+    /// Moves the value stored at old to new, overwriting whatever new held.
+    /// Keys below old stay where they are. Returns false if old holds no value.
+    pub fn rename(&mut self, old: &str, new: &str) -> bool {
+        if new.is_empty() {
+            warn!("Empty path provided, skipping rename");
+            return false;
+        }
+        info!("Renaming {} to {}", old, new);
+        let Some(node) = self.find_node(old) else {
+            return false;
+        };
+        let mut moved = Node::new(node.name);
+        moved.value = node.value.clone();
+        moved.score = node.score;
+        if moved.value.is_none() {
+            return false;
+        }
+        let value = moved.value.clone();
+        self.deep_delete(old);
+        Self::graft(self.root_mut(), new, moved);
+        self.record(new, value.as_deref());
+        true
+    }
+
+    /// Moves every key starting with old_prefix under new_prefix by detaching the
+    /// subtree and grafting it at the new place. Returns how many keys were moved.
+    pub fn rename_prefix(&mut self, old_prefix: &str, new_prefix: &str) -> usize {
+        if old_prefix.is_empty() || new_prefix.is_empty() {
+            warn!("Empty prefix provided, skipping rename prefix");
+            return 0;
+        }
+        info!("Renaming prefix {} to {}", old_prefix, new_prefix);
+        if self.is_versioned() {
+            // History stays with the old keys, so every key is moved on its own.
+            let entries: Vec<(String, String, u64)> = self
+                .valued_nodes(old_prefix)
+                .into_iter()
+                .map(|(key, node)| (key, node.value.clone().unwrap_or_default(), node.score))
+                .collect();
+            self.delete_prefix(old_prefix);
+            for (key, value, score) in entries.iter() {
+                let key = format!("{}{}", new_prefix, &key[old_prefix.len()..]);
+                self.insert(&key, value);
+                self.set_score(&key, *score);
+            }
+            return entries.len();
+        }
+        if self.find_node(old_prefix).is_none() {
+            return 0;
+        }
+        let Some(subtree) = Self::detach(self.root_mut(), old_prefix) else {
+            return 0;
+        };
+        let moved = subtree.count();
+        Self::graft(self.root_mut(), new_prefix, Arc::unwrap_or_clone(subtree));
+        moved
+    }
+
+    /// Deletes every key starting with the prefix and returns how many were removed.
+    pub fn delete_prefix(&mut self, prefix: &str) -> usize {
+        if prefix.is_empty() {
+            warn!("Empty prefix provided, skipping delete prefix");
+            return 0;
+        }
+        info!("Deleting prefix: {}", prefix);
+        if self.is_versioned() {
+            // Every key gets its deletion recorded, so they are deleted one by one.
+            let keys: Vec<String> = self
+                .valued_nodes(prefix)
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect();
+            for key in keys.iter() {
+                self.deep_delete(key);
+            }
+            return keys.len();
+        }
+        if self.find_node(prefix).is_none() {
+            return 0;
+        }
+        Self::detach(self.root_mut(), prefix).map_or(0, |node| node.count())
+    }
+
+    /// Returns keys starting with the prefix together with their nodes.
+    fn valued_nodes(&self, prefix: &str) -> Vec<(String, &Node)> {
+        let mut nodes = Vec::new();
+        match prefix.chars().last() {
+            None => {
+                for node in self.root.iter() {
+                    node.collect_nodes(String::new(), &mut nodes);
+                }
+            }
+            Some(name) => {
+                if let Some(node) = self.find_node(prefix) {
+                    let parent = &prefix[..prefix.len() - name.len_utf8()];
+                    node.collect_nodes(parent.to_string(), &mut nodes);
+                }
+            }
+        }
+        nodes
+    }
+
+    /// Counts keys starting with the prefix. An empty prefix counts the whole tree.
+    pub fn count_prefix(&self, prefix: &str) -> usize {
+        info!("Counting prefix: {}", prefix);
+        if prefix.is_empty() {
+            return self.root.iter().map(|node| node.count()).sum();
+        }
+        self.find_node(prefix).map_or(0, Node::count)
+    }
+
+    /// Returns a copy of the keys under the prefix, with the prefix stripped.
+    /// The value stored at the prefix itself would get an empty key, so it is left out.
+    pub fn subtree(&self, prefix: &str) -> CharTree {
+        info!("Copying subtree at prefix: {}", prefix);
+        let root = if prefix.is_empty() {
+            self.root.clone()
+        } else {
+            Arc::new(
+                self.find_node(prefix)
+                    .map(|node| node.children.clone())
+                    .unwrap_or_default(),
+            )
+        };
+        CharTree {
+            name: self.name.clone(),
+            root,
+            versioning: self.versioning.clone(),
+        }
+    }
+
     /// This function returns all possible keys and all possible values inserted.
     pub fn scan<'a>(&'a self) -> Vec<(String, &'a String)> {
         info!("Scanning tree {}", &self.name);
@@ -232,11 +800,11 @@ impl CharTree {
     }
 
     fn scan_recursive<'a>(
-        node: &'a Box<Node>,
+        node: &'a Node,
         mut path: String,
         result: &mut Vec<(String, &'a String)>,
     ) {
-        path.push(node.name.clone());
+        path.push(node.name);
         if let Some(value) = &node.value {
             result.push((path.clone(), value));
         }
@@ -267,7 +835,7 @@ mod tests {
     #[test]
     fn test_node() {
         let mut node = Node::new('a');
-        node.children.push(Box::new(Node::new('b')));
+        node.children.push(Arc::new(Node::new('b')));
         assert_eq!(node.get_child_mut('b').unwrap().name, 'b');
     }
 
@@ -277,6 +845,26 @@ mod tests {
         assert_eq!(tree.get("ab").unwrap(), "AB".to_string());
     }
 
+    #[test]
+    fn test_conditional_insert() {
+        let mut tree = setup_tree();
+        assert_eq!(tree.replace("lock", "owner1"), None);
+        assert_eq!(tree.get("lock"), None);
+        assert_eq!(tree.insert_if_absent("lock", "owner1"), None);
+        assert_eq!(
+            tree.insert_if_absent("lock", "owner2"),
+            Some("owner1".to_string())
+        );
+        assert_eq!(tree.get("lock").unwrap(), "owner1".to_string());
+        assert_eq!(tree.replace("lock", "owner2"), Some("owner1".to_string()));
+        assert_eq!(tree.get("lock").unwrap(), "owner2".to_string());
+        // Keys with values along their path are still absent.
+        assert_eq!(tree.insert_if_absent("abcdx", "X"), None);
+        assert_eq!(tree.insert_if_absent("abcd", "Y"), Some("ABCD".to_string()));
+        assert_eq!(tree.insert_if_absent("lo", "L"), None);
+        assert_eq!(tree.get("lo").unwrap(), "L".to_string());
+    }
+
     #[test]
     fn test_insert_and_hit() {
         let mut tree = setup_tree();
@@ -289,6 +877,188 @@ mod tests {
         assert_eq!(tree.hit("WAUAAAA").unwrap(), "car2".to_string());
     }
 
+    #[test]
+    fn test_hit_diverging_path() {
+        let mut tree = CharTree::new("test".to_string());
+        tree.insert("a", "A");
+        tree.insert("abcd", "ABCD");
+
+        assert_eq!(tree.hit("abcx").unwrap(), "A".to_string());
+        assert_eq!(tree.hit("abcdx").unwrap(), "ABCD".to_string());
+        assert_eq!(tree.hit("b"), None);
+    }
+
+    #[test]
+    fn test_hit_with_key() {
+        let mut tree = setup_tree();
+        tree.insert("10.0.", "net");
+        tree.insert("10.0.1.", "subnet");
+
+        assert_eq!(
+            tree.hit_with_key("10.0.1.7"),
+            Some(("10.0.1.".to_string(), "subnet".to_string()))
+        );
+        assert_eq!(
+            tree.hit_with_key("10.0.2.7"),
+            Some(("10.0.".to_string(), "net".to_string()))
+        );
+        assert_eq!(
+            tree.hit_with_key("abx"),
+            Some(("ab".to_string(), "AB".to_string()))
+        );
+        assert_eq!(tree.hit_with_key("10.1"), None);
+        assert_eq!(tree.hit_with_key(""), None);
+    }
+
+    #[test]
+    fn test_hit_all() {
+        let mut tree = setup_tree();
+        tree.insert("Šš", "ŠŠ");
+
+        assert_eq!(tree.hit_all("abcx"), vec!["A", "AB", "ABC"]);
+        assert_eq!(tree.hit_all("dcba"), vec!["D", "DC"]);
+        assert_eq!(tree.hit_all("Ššx"), vec!["ŠŠ"]);
+        assert!(tree.hit_all("x").is_empty());
+    }
+
+    #[test]
+    fn test_complete() {
+        let mut tree = setup_tree();
+        tree.insert("abx", "ABX");
+        tree.set_score("abcd", 5);
+        tree.set_score("abx", 3);
+        assert_eq!(tree.add_score("ab", 2), Some(2));
+        assert_eq!(tree.add_score("ab", 2), Some(4));
+        assert!(!tree.set_score("abz", 1));
+
+        assert_eq!(tree.complete("ab", 3), vec!["abcd", "ab", "abx"]);
+        assert_eq!(tree.complete("ab", 10), vec!["abcd", "ab", "abx", "abc"]);
+        assert_eq!(tree.complete("", 2), vec!["abcd", "ab"]);
+        assert_eq!(tree.complete("d", 5), vec!["d", "dc"]);
+        assert!(tree.complete("x", 5).is_empty());
+
+        tree.deep_delete("abcd");
+        assert_eq!(tree.complete("a", 2), vec!["ab", "abx"]);
+        tree.insert("abcd", "ABCD");
+        assert_eq!(tree.complete("abc", 2), vec!["abc", "abcd"]);
+    }
+
+    #[test]
+    fn test_fuzzy() {
+        let mut tree = setup_tree();
+        tree.insert("ŠšŠ", "ŪūŪ");
+
+        assert_eq!(
+            tree.fuzzy("abd", 1),
+            vec![
+                ("ab".to_string(), 1),
+                ("abc".to_string(), 1),
+                ("abcd".to_string(), 1)
+            ]
+        );
+        assert_eq!(tree.fuzzy("abcd", 0), vec![("abcd".to_string(), 0)]);
+        assert_eq!(tree.fuzzy("Šxš", 2), vec![("ŠšŠ".to_string(), 2)]);
+        assert!(tree.fuzzy("xyz", 1).is_empty());
+    }
+
+    #[test]
+    fn test_prefix_operations() {
+        let mut tree = setup_tree();
+        tree.insert("tenant42/a", "1");
+        tree.insert("tenant42/b/c", "2");
+        tree.insert("tenant4", "3");
+        tree.set_score("tenant42/a", 9);
+
+        assert_eq!(tree.count_prefix("tenant4"), 3);
+        assert_eq!(tree.count_prefix("tenant42/"), 2);
+        assert_eq!(tree.count_prefix("ab"), 3);
+        assert_eq!(tree.count_prefix(""), 9);
+        assert_eq!(tree.count_prefix("x"), 0);
+
+        let subtree = tree.subtree("tenant42/");
+        assert_eq!(subtree.get("a").unwrap(), "1".to_string());
+        assert_eq!(subtree.get("b/c").unwrap(), "2".to_string());
+        assert_eq!(subtree.count_prefix(""), 2);
+        assert_eq!(subtree.complete("", 1), vec!["a"]);
+
+        assert_eq!(tree.delete_prefix("tenant42/"), 2);
+        assert_eq!(tree.get("tenant42/a"), None);
+        assert_eq!(tree.get("tenant4").unwrap(), "3".to_string());
+        assert_eq!(tree.complete("t", 5), vec!["tenant4"]);
+        assert_eq!(tree.delete_prefix("tenant"), 1);
+        assert_eq!(tree.delete_prefix("tenant"), 0);
+        assert_eq!(tree.scan().len(), 6);
+    }
+
+    #[test]
+    fn test_rename() {
+        let mut tree = setup_tree();
+        tree.set_score("ab", 4);
+
+        assert!(tree.rename("ab", "x"));
+        assert_eq!(tree.get("ab"), None);
+        assert_eq!(tree.get("abc").unwrap(), "ABC".to_string());
+        assert_eq!(tree.get("x").unwrap(), "AB".to_string());
+        assert_eq!(tree.complete("", 1), vec!["x"]);
+        assert!(!tree.rename("ab", "y"));
+        assert!(tree.rename("d", "dc"));
+        assert_eq!(tree.get("dc").unwrap(), "D".to_string());
+        assert_eq!(tree.get("d"), None);
+    }
+
+    #[test]
+    fn test_rename_prefix() {
+        let mut tree = setup_tree();
+        tree.insert("xc", "XC");
+
+        assert_eq!(tree.rename_prefix("ab", "x"), 3);
+        assert_eq!(tree.get("a").unwrap(), "A".to_string());
+        assert_eq!(tree.get("ab"), None);
+        assert_eq!(tree.get("x").unwrap(), "AB".to_string());
+        assert_eq!(tree.get("xc").unwrap(), "ABC".to_string());
+        assert_eq!(tree.get("xcd").unwrap(), "ABCD".to_string());
+
+        assert_eq!(tree.rename_prefix("a", "aa"), 1);
+        assert_eq!(tree.get("aa").unwrap(), "A".to_string());
+        assert_eq!(tree.rename_prefix("missing", "m"), 0);
+        assert_eq!(tree.scan().len(), 6);
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let mut tree = setup_tree();
+        let snapshot = tree.snapshot();
+
+        tree.insert("ab", "changed");
+        tree.insert("x", "X");
+        tree.deep_delete("dc");
+        tree.rename_prefix("abc", "y");
+        tree.set_score("a", 3);
+        assert_eq!(snapshot.scan(), setup_tree().scan());
+        assert_eq!(snapshot.complete("", 1), vec!["a"]);
+
+        let mut copy = snapshot.snapshot();
+        copy.delete_prefix("a");
+        assert_eq!(snapshot.get("ab").unwrap(), "AB".to_string());
+        assert_eq!(tree.get("ab").unwrap(), "changed".to_string());
+        assert_eq!(copy.get("ab"), None);
+    }
+
+    #[test]
+    fn test_missing_keys_keep_sharing() {
+        let mut tree = setup_tree();
+        let snapshot = tree.snapshot();
+
+        tree.deep_delete("abx");
+        tree.deep_delete("x");
+        tree.shallow_delete("abx");
+        assert_eq!(tree.delete_prefix("abx"), 0);
+        assert_eq!(tree.rename_prefix("abx", "y"), 0);
+        assert!(!tree.set_score("abx", 1));
+        assert_eq!(tree.add_score("dx", 1), None);
+        assert!(Arc::ptr_eq(&tree.root, &snapshot.root));
+    }
+
     #[test]
     fn test_deep_delete() {
         let mut tree = setup_tree();
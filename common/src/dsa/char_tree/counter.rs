@@ -0,0 +1,113 @@
+//! Numeric counters stored in the values of a [`CharTree`].
+//!
+//! A counter is an ordinary value holding a decimal number. A missing key counts as zero,
+//! so the first increment creates it.
+
+use std::fmt;
+
+use log::info;
+
+use super::CharTree;
+
+#[derive(Debug, PartialEq)]
+pub enum CounterError {
+    EmptyKey,
+    NotAnInteger,
+    NotAFloat,
+    Overflow,
+}
+
+impl fmt::Display for CounterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CounterError::EmptyKey => write!(f, "empty key"),
+            CounterError::NotAnInteger => write!(f, "value is not an integer"),
+            CounterError::NotAFloat => write!(f, "value is not a float"),
+            CounterError::Overflow => write!(f, "increment would overflow"),
+        }
+    }
+}
+
+impl CharTree {
+    /// Adds delta to the integer stored under the key and returns the new value.
+    pub fn incr_by(&mut self, key: &str, delta: i64) -> Result<i64, CounterError> {
+        if key.is_empty() {
+            return Err(CounterError::EmptyKey);
+        }
+        info!("Incrementing {} by {}", key, delta);
+        let current = match self.get(key) {
+            Some(value) => value
+                .parse::<i64>()
+                .map_err(|_| CounterError::NotAnInteger)?,
+            None => 0,
+        };
+        let new = current.checked_add(delta).ok_or(CounterError::Overflow)?;
+        self.insert(key, &new.to_string());
+        Ok(new)
+    }
+
+    /// Adds delta to the number stored under the key and returns the new value.
+    pub fn incr_by_float(&mut self, key: &str, delta: f64) -> Result<f64, CounterError> {
+        if key.is_empty() {
+            return Err(CounterError::EmptyKey);
+        }
+        info!("Incrementing {} by {}", key, delta);
+        let current = match self.get(key) {
+            Some(value) => value
+                .parse::<f64>()
+                .ok()
+                .filter(|current| current.is_finite())
+                .ok_or(CounterError::NotAFloat)?,
+            None => 0.0,
+        };
+        let new = current + delta;
+        if !new.is_finite() {
+            return Err(CounterError::Overflow);
+        }
+        self.insert(key, &new.to_string());
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_incr_by() {
+        let mut tree = CharTree::new("test".to_string());
+        assert_eq!(tree.incr_by("hits/a", 1), Ok(1));
+        assert_eq!(tree.incr_by("hits/a", 5), Ok(6));
+        assert_eq!(tree.incr_by("hits/a", -10), Ok(-4));
+        assert_eq!(tree.get("hits/a").unwrap(), "-4".to_string());
+
+        tree.insert("hits/b", "many");
+        assert_eq!(tree.incr_by("hits/b", 1), Err(CounterError::NotAnInteger));
+        assert_eq!(tree.get("hits/b").unwrap(), "many".to_string());
+
+        tree.insert("hits/c", &i64::MAX.to_string());
+        assert_eq!(tree.incr_by("hits/c", 1), Err(CounterError::Overflow));
+        assert_eq!(tree.incr_by("", 1), Err(CounterError::EmptyKey));
+    }
+
+    #[test]
+    fn test_incr_by_float() {
+        let mut tree = CharTree::new("test".to_string());
+        assert_eq!(tree.incr_by_float("load", 0.5), Ok(0.5));
+        tree.insert("count", "2");
+        assert_eq!(tree.incr_by_float("count", 1.25), Ok(3.25));
+        assert_eq!(tree.get("count").unwrap(), "3.25".to_string());
+        assert_eq!(tree.incr_by("count", 1), Err(CounterError::NotAnInteger));
+
+        tree.insert("name", "nan");
+        assert_eq!(
+            tree.incr_by_float("name", 1.0),
+            Err(CounterError::NotAFloat)
+        );
+        assert_eq!(tree.incr_by_float("load", f64::MAX), Ok(f64::MAX + 0.5));
+        assert_eq!(
+            tree.incr_by_float("load", f64::MAX),
+            Err(CounterError::Overflow)
+        );
+    }
+}
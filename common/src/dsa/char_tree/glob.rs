@@ -0,0 +1,282 @@
+//! Glob matching over the keys of a [`CharTree`].
+//!
+//! Supported syntax: `?` matches any single char, `*` any sequence of chars,
+//! `[a-z]` a char class (`[!a-z]` or `[^a-z]` negates it) and `\` escapes the next char.
+//! The pattern is matched while walking the tree, so literal parts of the pattern
+//! only descend into the matching children instead of filtering a full scan.
+
+use std::fmt;
+
+use std::sync::Arc;
+
+use log::info;
+
+use super::{CharTree, Node};
+
+#[derive(Debug, PartialEq)]
+pub enum PatternError {
+    UnclosedClass,
+    TrailingEscape,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatternError::UnclosedClass => write!(f, "unclosed character class"),
+            PatternError::TrailingEscape => write!(f, "pattern ends with an escape"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Literal(char),
+    Any,
+    Star,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+impl Token {
+    fn matches(&self, name: char) -> bool {
+        match self {
+            Token::Literal(c) => *c == name,
+            Token::Any | Token::Star => true,
+            Token::Class { ranges, negated } => {
+                ranges
+                    .iter()
+                    .any(|(from, to)| (*from..=*to).contains(&name))
+                    != *negated
+            }
+        }
+    }
+}
+
+fn parse(pattern: &str) -> Result<Vec<Token>, PatternError> {
+    let mut tokens = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '?' => Token::Any,
+            '*' => {
+                // Consecutive stars are the same as one.
+                if tokens.last() == Some(&Token::Star) {
+                    continue;
+                }
+                Token::Star
+            }
+            '\\' => Token::Literal(chars.next().ok_or(PatternError::TrailingEscape)?),
+            '[' => {
+                let negated = chars.next_if(|&c| c == '!' || c == '^').is_some();
+                let mut ranges = Vec::new();
+                loop {
+                    let from = match chars.next().ok_or(PatternError::UnclosedClass)? {
+                        ']' if !ranges.is_empty() => break,
+                        '\\' => chars.next().ok_or(PatternError::TrailingEscape)?,
+                        c => c,
+                    };
+                    let to = match chars.peek() {
+                        Some('-') => {
+                            chars.next();
+                            match chars.next().ok_or(PatternError::UnclosedClass)? {
+                                // A trailing dash is taken literally, as in `[a-]`.
+                                ']' => {
+                                    ranges.push((from, from));
+                                    ranges.push(('-', '-'));
+                                    break;
+                                }
+                                '\\' => chars.next().ok_or(PatternError::TrailingEscape)?,
+                                c => c,
+                            }
+                        }
+                        _ => from,
+                    };
+                    ranges.push((from, to));
+                }
+                Token::Class { ranges, negated }
+            }
+            c => Token::Literal(c),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// A set of positions in the pattern, like the states of an NFA.
+type States = Vec<usize>;
+
+/// Adds the positions reachable without consuming a char: a star may match nothing.
+fn closure(tokens: &[Token], mut states: States) -> States {
+    let mut i = 0;
+    while i < states.len() {
+        let state = states[i];
+        if tokens.get(state) == Some(&Token::Star) && !states.contains(&(state + 1)) {
+            states.push(state + 1);
+        }
+        i += 1;
+    }
+    states.sort_unstable();
+    states
+}
+
+fn step(tokens: &[Token], states: &States, name: char) -> States {
+    let mut next = Vec::new();
+    for &state in states.iter() {
+        let Some(token) = tokens.get(state) else {
+            continue;
+        };
+        if !token.matches(name) {
+            continue;
+        }
+        let target = if *token == Token::Star {
+            state
+        } else {
+            state + 1
+        };
+        if !next.contains(&target) {
+            next.push(target);
+        }
+    }
+    closure(tokens, next)
+}
+
+/// If every live state expects a literal, those are the only children worth visiting.
+fn literal_names(tokens: &[Token], states: &States) -> Option<Vec<char>> {
+    let mut names = Vec::new();
+    for &state in states.iter() {
+        match tokens.get(state) {
+            Some(Token::Literal(c)) => names.push(*c),
+            None => {}
+            Some(_) => return None,
+        }
+    }
+    Some(names)
+}
+
+impl CharTree {
+    /// Returns all keys and values matching the glob pattern.
+    pub fn glob<'a>(&'a self, pattern: &str) -> Result<Vec<(String, &'a String)>, PatternError> {
+        info!("Matching pattern {} in tree {}", pattern, &self.name);
+        let tokens = parse(pattern)?;
+        let mut result = Vec::new();
+        let states = closure(&tokens, vec![0]);
+        Self::glob_children(&self.root, &tokens, &states, String::new(), &mut result);
+        Ok(result)
+    }
+
+    fn glob_children<'a>(
+        nodes: &'a [Arc<Node>],
+        tokens: &[Token],
+        states: &States,
+        path: String,
+        result: &mut Vec<(String, &'a String)>,
+    ) {
+        match literal_names(tokens, states) {
+            Some(names) => {
+                for node in nodes.iter().filter(|n| names.contains(&n.name)) {
+                    Self::glob_recursive(node, tokens, states, path.clone(), result);
+                }
+            }
+            None => {
+                for node in nodes.iter() {
+                    Self::glob_recursive(node, tokens, states, path.clone(), result);
+                }
+            }
+        }
+    }
+
+    fn glob_recursive<'a>(
+        node: &'a Node,
+        tokens: &[Token],
+        states: &States,
+        mut path: String,
+        result: &mut Vec<(String, &'a String)>,
+    ) {
+        let states = step(tokens, states, node.name);
+        if states.is_empty() {
+            return;
+        }
+        path.push(node.name);
+        if let Some(value) = &node.value {
+            if states.contains(&tokens.len()) {
+                result.push((path.clone(), value));
+            }
+        }
+        Self::glob_children(&node.children, tokens, &states, path, result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_tree() -> CharTree {
+        let mut tree = CharTree::new("test".to_string());
+        for key in [
+            "abc",
+            "abd",
+            "a*c",
+            "axc",
+            "b",
+            "bc",
+            "ŠšŠ",
+            "tenant42/x",
+            "tenant7/y",
+        ] {
+            tree.insert(key, &key.to_uppercase());
+        }
+        tree
+    }
+
+    fn keys(tree: &CharTree, pattern: &str) -> Vec<String> {
+        let mut keys: Vec<String> = tree
+            .glob(pattern)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_glob() {
+        let tree = setup_tree();
+        assert_eq!(keys(&tree, "abc"), vec!["abc"]);
+        assert_eq!(keys(&tree, "ab?"), vec!["abc", "abd"]);
+        assert_eq!(keys(&tree, "a*c"), vec!["a*c", "abc", "axc"]);
+        assert_eq!(keys(&tree, "a\\*c"), vec!["a*c"]);
+        assert_eq!(keys(&tree, "b*"), vec!["b", "bc"]);
+        assert_eq!(keys(&tree, "**c"), vec!["a*c", "abc", "axc", "bc"]);
+        assert_eq!(keys(&tree, "a[b-x]c"), vec!["abc", "axc"]);
+        assert_eq!(keys(&tree, "a[!b]c"), vec!["a*c", "axc"]);
+        assert_eq!(keys(&tree, "[Š]*"), vec!["ŠšŠ"]);
+        assert_eq!(
+            keys(&tree, "tenant[0-9]*/*"),
+            vec!["tenant42/x", "tenant7/y"]
+        );
+        assert!(keys(&tree, "").is_empty());
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("[a-]").unwrap(),
+            vec![Token::Class {
+                ranges: vec![('a', 'a'), ('-', '-')],
+                negated: false,
+            }]
+        );
+        assert_eq!(
+            parse("[]a]").unwrap(),
+            vec![Token::Class {
+                ranges: vec![(']', ']'), ('a', 'a')],
+                negated: false,
+            }]
+        );
+        assert_eq!(parse("[a-z"), Err(PatternError::UnclosedClass));
+        assert_eq!(parse("ab\\"), Err(PatternError::TrailingEscape));
+    }
+}
@@ -0,0 +1,259 @@
+//! Optional multi-version history of a [`CharTree`].
+//!
+//! A versioned tree numbers every change with a sequence number and keeps the last
+//! few versions of each key on its node, together with the time of the change.
+//! Deleted keys keep their history, so their nodes stay in the tree without a value,
+//! until more than [`DELETED_KEYS_LIMIT`] other keys were deleted after them.
+
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::info;
+
+use super::CharTree;
+
+/// One version of a key. A `None` value means the key was deleted.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Version {
+    pub seq: u64,
+    /// Milliseconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub value: Option<String>,
+}
+
+/// A point in the history of a tree, either a sequence number or a timestamp in milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VersionPoint {
+    Seq(u64),
+    Time(u64),
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub(super) struct Versioning {
+    /// How many versions are kept per key.
+    limit: usize,
+    /// Sequence number of the last change.
+    seq: u64,
+    /// Deleted keys with the sequence number of their deletion, oldest first.
+    #[cfg_attr(feature = "serde", serde(default))]
+    deleted: VecDeque<(u64, String)>,
+}
+
+/// How many deleted keys keep their history. The history of older ones is dropped with
+/// their nodes, so deleting keys doesn't make the tree grow without bound.
+pub const DELETED_KEYS_LIMIT: usize = 1024;
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+impl CharTree {
+    /// Creates a tree that keeps up to history_limit versions of every key.
+    pub fn new_versioned(name: String, history_limit: usize) -> Self {
+        let mut tree = CharTree::new(name);
+        tree.versioning = Some(Versioning {
+            limit: history_limit.max(1),
+            seq: 0,
+            deleted: VecDeque::new(),
+        });
+        tree
+    }
+
+    pub fn is_versioned(&self) -> bool {
+        self.versioning.is_some()
+    }
+
+    /// Appends a version to the key's history, dropping the oldest one if the history is full.
+    /// Does nothing for trees without versioning or keys without a node.
+    pub(super) fn record(&mut self, key: &str, value: Option<&str>) {
+        let Some(versioning) = self.versioning.as_mut() else {
+            return;
+        };
+        versioning.seq += 1;
+        let version = Version {
+            seq: versioning.seq,
+            timestamp: now_millis(),
+            value: value.map(str::to_string),
+        };
+        let limit = versioning.limit;
+        if version.value.is_none() {
+            versioning.deleted.push_back((version.seq, key.to_string()));
+        }
+        let Some(node) = self.find_node_mut(key) else {
+            return;
+        };
+        info!("Recording version {} of key {}", version.seq, key);
+        if node.history.len() >= limit {
+            node.history.pop_front();
+        }
+        node.history.push_back(version);
+        self.prune_deleted();
+    }
+
+    /// Drops the history of the oldest deleted keys beyond [`DELETED_KEYS_LIMIT`],
+    /// together with the nodes only kept for it. Keys written since they were deleted are kept.
+    fn prune_deleted(&mut self) {
+        while let Some(versioning) = self.versioning.as_mut() {
+            if versioning.deleted.len() <= DELETED_KEYS_LIMIT {
+                return;
+            }
+            let Some((seq, key)) = versioning.deleted.pop_front() else {
+                return;
+            };
+            let still_deleted = self
+                .find_node(&key)
+                .and_then(|node| node.history.back())
+                .is_some_and(|version| version.seq == seq);
+            if !still_deleted {
+                continue;
+            }
+            info!("Dropping history of deleted key {}", key);
+            if let Some(node) = self.find_node_mut(&key) {
+                node.history.clear();
+            }
+            // Removes the node and its ancestors if nothing else keeps them.
+            self.deep_delete(&key);
+        }
+    }
+
+    /// Records the deletion of the key, if it currently holds a value.
+    pub(super) fn record_delete(&mut self, key: &str) {
+        if self.is_versioned() && self.find_node(key).is_some_and(|n| n.value.is_some()) {
+            self.record(key, None);
+        }
+    }
+
+    /// Returns the retained versions of the key, oldest first.
+    pub fn history(&self, key: &str) -> Vec<Version> {
+        self.find_node(key)
+            .map(|node| node.history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Returns the value the key had at the given point. Returns None if the key did not
+    /// exist then, or if the point is older than the retained history.
+    pub fn get_at(&self, key: &str, point: VersionPoint) -> Option<String> {
+        info!("Getting value of {} at {:?}", key, point);
+        let node = self.find_node(key)?;
+        node.history
+            .iter()
+            .rev()
+            .find(|version| match point {
+                VersionPoint::Seq(seq) => version.seq <= seq,
+                VersionPoint::Time(timestamp) => version.timestamp <= timestamp,
+            })
+            .and_then(|version| version.value.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut tree = CharTree::new_versioned("test".to_string(), 3);
+        tree.insert("a", "1");
+        tree.insert("ab", "2");
+        tree.insert("a", "3");
+        tree.deep_delete("a");
+        tree.deep_delete("a");
+
+        let history = tree.history("a");
+        assert_eq!(
+            history
+                .iter()
+                .map(|v| (v.seq, v.value.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, Some("1".to_string())),
+                (3, Some("3".to_string())),
+                (4, None)
+            ]
+        );
+        assert_eq!(tree.get("a"), None);
+        assert_eq!(
+            tree.get_at("a", VersionPoint::Seq(1)),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            tree.get_at("a", VersionPoint::Seq(2)),
+            Some("1".to_string())
+        );
+        assert_eq!(
+            tree.get_at("a", VersionPoint::Seq(3)),
+            Some("3".to_string())
+        );
+        assert_eq!(tree.get_at("a", VersionPoint::Seq(4)), None);
+        assert_eq!(tree.get_at("a", VersionPoint::Seq(0)), None);
+        assert_eq!(tree.get_at("a", VersionPoint::Time(0)), None);
+        assert_eq!(tree.get_at("a", VersionPoint::Time(u64::MAX)), None);
+        assert_eq!(
+            tree.get_at("ab", VersionPoint::Time(u64::MAX)),
+            Some("2".to_string())
+        );
+        assert_eq!(tree.scan().len(), 1);
+
+        tree.insert("a", "5");
+        tree.insert("a", "6");
+        assert_eq!(tree.history("a").len(), 3);
+        assert_eq!(tree.get_at("a", VersionPoint::Seq(1)), None);
+    }
+
+    #[test]
+    fn test_history_of_prefix_operations() {
+        let mut tree = CharTree::new_versioned("test".to_string(), 10);
+        tree.insert("tenant/a", "1");
+        tree.insert("tenant/b", "2");
+        tree.set_score("tenant/a", 5);
+
+        assert_eq!(tree.rename_prefix("tenant/", "t/"), 2);
+        assert_eq!(tree.get("t/a").unwrap(), "1".to_string());
+        assert_eq!(tree.complete("t", 1), vec!["t/a"]);
+        assert_eq!(
+            tree.get_at("tenant/a", VersionPoint::Seq(2)),
+            Some("1".to_string())
+        );
+        assert_eq!(tree.history("tenant/a").last().unwrap().value, None);
+
+        assert!(tree.rename("t/a", "x"));
+        assert_eq!(tree.history("x").len(), 1);
+        assert_eq!(tree.delete_prefix("t"), 1);
+        assert_eq!(tree.history("t/b").len(), 2);
+        assert!(tree.scan().iter().all(|(key, _)| key == "x"));
+    }
+
+    #[test]
+    fn test_deleted_keys_are_pruned() {
+        let mut tree = CharTree::new_versioned("test".to_string(), 2);
+        tree.insert("kept", "1");
+        tree.deep_delete("kept");
+        tree.insert("kept", "2");
+        for i in 0..DELETED_KEYS_LIMIT + 10 {
+            let key = format!("k{}", i);
+            tree.insert(&key, "1");
+            tree.deep_delete(&key);
+        }
+        // The oldest deletions are dropped with their nodes, newer ones are kept.
+        assert!(tree.find_node("k0").is_none());
+        assert!(tree.history("k9").is_empty());
+        assert_eq!(tree.history("k10").len(), 2);
+        assert_eq!(tree.history("kept").len(), 2);
+        assert_eq!(tree.get("kept").unwrap(), "2".to_string());
+    }
+
+    #[test]
+    fn test_unversioned_tree_has_no_history() {
+        let mut tree = CharTree::new("test".to_string());
+        tree.insert("a", "1");
+        tree.deep_delete("a");
+        assert!(tree.history("a").is_empty());
+        assert_eq!(tree.get_at("a", VersionPoint::Seq(1)), None);
+        assert!(tree.root.is_empty());
+    }
+}
@@ -0,0 +1,199 @@
+//! Operations between two trees. Both tries are walked in lockstep,
+//! so only nodes that exist in at least one of them are visited.
+
+use std::sync::Arc;
+
+use log::info;
+
+use super::{CharTree, Children, Node};
+
+/// What merge() does with keys present in both trees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergePolicy {
+    Overwrite,
+    Keep,
+}
+
+/// Keys that differ between two trees, as seen from the first one.
+#[derive(Debug, Default, PartialEq)]
+pub struct TreeDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+fn collect_keys(node: &Node, path: String, keys: &mut Vec<String>) {
+    let mut nodes = Vec::new();
+    node.collect_nodes(path, &mut nodes);
+    keys.extend(nodes.into_iter().map(|(key, _)| key));
+}
+
+impl CharTree {
+    /// Returns a copy of the tree under a new name.
+    pub fn copy(&self, name: String) -> CharTree {
+        info!("Copying tree {} to {}", &self.name, name);
+        CharTree {
+            name,
+            root: Arc::clone(&self.root),
+            versioning: self.versioning.clone(),
+        }
+    }
+
+    /// Merges all keys of the other tree into this one and returns how many were written.
+    pub fn merge(&mut self, other: &CharTree, policy: MergePolicy) -> usize {
+        info!("Merging tree {} into {}", &other.name, &self.name);
+        if self.is_versioned() {
+            // Written keys have to be recorded one by one.
+            let mut written = 0;
+            for (key, node) in other.valued_nodes("") {
+                if policy == MergePolicy::Overwrite || self.get(&key).is_none() {
+                    self.insert(&key, node.value.as_deref().unwrap_or_default());
+                    self.set_score(&key, node.score);
+                    written += 1;
+                }
+            }
+            return written;
+        }
+        Self::merge_children(self.root_mut(), &other.root, policy)
+    }
+
+    fn merge_children(into: &mut Children, from: &[Arc<Node>], policy: MergePolicy) -> usize {
+        let mut written = 0;
+        for node in from.iter() {
+            match into.iter_mut().find(|n| n.name == node.name) {
+                // Nothing to merge if both trees still share the subtree, but its keys
+                // count as written like any other equal key.
+                Some(existing) if Arc::ptr_eq(existing, node) => {
+                    if policy == MergePolicy::Overwrite {
+                        written += node.count();
+                    }
+                }
+                Some(existing) => {
+                    let existing = Arc::make_mut(existing);
+                    if node.value.is_some()
+                        && (existing.value.is_none() || policy == MergePolicy::Overwrite)
+                    {
+                        existing.value = node.value.clone();
+                        existing.score = node.score;
+                        written += 1;
+                    }
+                    written += Self::merge_children(&mut existing.children, &node.children, policy);
+                    existing.update_max_score();
+                }
+                None => {
+                    written += node.count();
+                    into.push(Arc::clone(node));
+                }
+            }
+        }
+        written
+    }
+
+    /// Compares the tree with another one: keys only in the other tree are added,
+    /// keys only in this tree are removed and keys with different values are changed.
+    pub fn diff(&self, other: &CharTree) -> TreeDiff {
+        info!("Diffing tree {} with {}", &self.name, &other.name);
+        let mut diff = TreeDiff::default();
+        Self::diff_children(&self.root, &other.root, String::new(), &mut diff);
+        diff
+    }
+
+    fn diff_children(left: &[Arc<Node>], right: &[Arc<Node>], path: String, diff: &mut TreeDiff) {
+        for node in left.iter() {
+            match right.iter().find(|n| n.name == node.name) {
+                // Shared subtrees, e.g. between a tree and its snapshot, are equal.
+                Some(other) if Arc::ptr_eq(node, other) => {}
+                Some(other) => {
+                    let mut path = path.clone();
+                    path.push(node.name);
+                    match (&node.value, &other.value) {
+                        (Some(a), Some(b)) if a != b => diff.changed.push(path.clone()),
+                        (Some(_), None) => diff.removed.push(path.clone()),
+                        (None, Some(_)) => diff.added.push(path.clone()),
+                        _ => {}
+                    }
+                    Self::diff_children(&node.children, &other.children, path, diff);
+                }
+                None => collect_keys(node, path.clone(), &mut diff.removed),
+            }
+        }
+        for node in right.iter() {
+            if !left.iter().any(|n| n.name == node.name) {
+                collect_keys(node, path.clone(), &mut diff.added);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(name: &str, entries: &[(&str, &str)]) -> CharTree {
+        let mut tree = CharTree::new(name.to_string());
+        for (key, value) in entries {
+            tree.insert(key, value);
+        }
+        tree
+    }
+
+    #[test]
+    fn test_copy() {
+        let staging = tree("staging", &[("a", "1"), ("ab", "2")]);
+        let mut production = staging.copy("production".to_string());
+        production.insert("a", "3");
+        assert_eq!(production.name, "production".to_string());
+        assert_eq!(production.get("ab").unwrap(), "2".to_string());
+        assert_eq!(staging.get("a").unwrap(), "1".to_string());
+    }
+
+    #[test]
+    fn test_merge() {
+        let staging = tree("staging", &[("a", "new"), ("abc", "3"), ("x", "4")]);
+
+        let mut production = tree("production", &[("a", "old"), ("ab", "2")]);
+        assert_eq!(production.merge(&staging, MergePolicy::Keep), 2);
+        assert_eq!(production.get("a").unwrap(), "old".to_string());
+        assert_eq!(production.get("ab").unwrap(), "2".to_string());
+        assert_eq!(production.get("abc").unwrap(), "3".to_string());
+        assert_eq!(production.get("x").unwrap(), "4".to_string());
+
+        let mut production = tree("production", &[("a", "old"), ("ab", "2")]);
+        assert_eq!(production.merge(&staging, MergePolicy::Overwrite), 3);
+        assert_eq!(production.get("a").unwrap(), "new".to_string());
+        assert_eq!(production.scan().len(), 4);
+    }
+
+    #[test]
+    fn test_merge_count_ignores_sharing() {
+        let staging = tree("staging", &[("a", "1"), ("ab", "2"), ("x", "3")]);
+        let mut copy = staging.copy("copy".to_string());
+        let mut rebuilt = tree("rebuilt", &[("a", "1"), ("ab", "2"), ("x", "3")]);
+        for policy in [MergePolicy::Overwrite, MergePolicy::Keep] {
+            assert_eq!(
+                copy.merge(&staging, policy),
+                rebuilt.merge(&staging, policy)
+            );
+        }
+        assert_eq!(copy.merge(&staging, MergePolicy::Overwrite), 3);
+    }
+
+    #[test]
+    fn test_diff() {
+        let staging = tree(
+            "staging",
+            &[("a", "1"), ("ab", "2"), ("abc", "3"), ("x", "4")],
+        );
+        let production = tree("production", &[("a", "1"), ("ab", "changed"), ("abd", "5")]);
+
+        assert_eq!(
+            staging.diff(&production),
+            TreeDiff {
+                added: vec!["abd".to_string()],
+                removed: vec!["abc".to_string(), "x".to_string()],
+                changed: vec!["ab".to_string()],
+            }
+        );
+        assert_eq!(staging.diff(&staging), TreeDiff::default());
+    }
+}
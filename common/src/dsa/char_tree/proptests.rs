@@ -0,0 +1,370 @@
+//! Property-based tests comparing [`CharTree`] against a reference model over `BTreeMap`.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use proptest::prelude::*;
+
+use super::{CharTree, MergePolicy, Node, TreeDiff};
+
+/// The reference model: a plain map where `hit` is defined as
+/// "value of the longest stored key that is a prefix of the query".
+#[derive(Default)]
+struct Model {
+    map: BTreeMap<String, String>,
+    scores: BTreeMap<String, u64>,
+}
+
+impl Model {
+    fn insert(&mut self, key: &str, value: &str) {
+        if !key.is_empty() {
+            self.map.insert(key.to_string(), value.to_string());
+        }
+    }
+
+    fn deep_delete(&mut self, key: &str) {
+        self.map.remove(key);
+        self.scores.remove(key);
+    }
+
+    fn delete_prefix(&mut self, prefix: &str) -> usize {
+        let keys: Vec<String> = self
+            .map
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect();
+        for key in keys.iter() {
+            self.deep_delete(key);
+        }
+        keys.len()
+    }
+
+    fn rename(&mut self, old: &str, new: &str) -> bool {
+        if new.is_empty() {
+            return false;
+        }
+        let Some(value) = self.map.remove(old) else {
+            return false;
+        };
+        let score = self.scores.remove(old);
+        self.deep_delete(new);
+        self.map.insert(new.to_string(), value);
+        if let Some(score) = score {
+            self.scores.insert(new.to_string(), score);
+        }
+        true
+    }
+
+    fn rename_prefix(&mut self, old: &str, new: &str) -> usize {
+        let keys: Vec<String> = self
+            .map
+            .keys()
+            .filter(|key| key.starts_with(old))
+            .cloned()
+            .collect();
+        let moved: Vec<(String, String, Option<u64>)> = keys
+            .iter()
+            .map(|key| {
+                let value = self.map.remove(key).unwrap();
+                let score = self.scores.remove(key);
+                (format!("{}{}", new, &key[old.len()..]), value, score)
+            })
+            .collect();
+        for (key, value, score) in moved {
+            self.deep_delete(&key);
+            self.map.insert(key.clone(), value);
+            if let Some(score) = score {
+                self.scores.insert(key, score);
+            }
+        }
+        keys.len()
+    }
+
+    fn count_prefix(&self, prefix: &str) -> usize {
+        self.map
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .count()
+    }
+
+    fn set_score(&mut self, key: &str, score: u64) -> bool {
+        if !self.map.contains_key(key) {
+            return false;
+        }
+        self.scores.insert(key.to_string(), score);
+        true
+    }
+
+    fn complete(&self, prefix: &str, k: usize) -> Vec<String> {
+        let mut keys: Vec<(u64, &String)> = self
+            .map
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .map(|key| (self.scores.get(key).copied().unwrap_or(0), key))
+            .collect();
+        keys.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+        keys.into_iter()
+            .take(k)
+            .map(|(_, key)| key.clone())
+            .collect()
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.map.get(key).cloned()
+    }
+
+    fn hit_all(&self, query: &str) -> Vec<(String, String)> {
+        let mut prefixes: Vec<(String, String)> = self
+            .map
+            .iter()
+            .filter(|(key, _)| query.starts_with(key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        prefixes.sort_by_key(|(key, _)| key.len());
+        prefixes
+    }
+
+    fn hit_with_key(&self, query: &str) -> Option<(String, String)> {
+        self.hit_all(query).pop()
+    }
+
+    fn hit(&self, query: &str) -> Option<String> {
+        self.hit_with_key(query).map(|(_, value)| value)
+    }
+
+    fn fuzzy(&self, query: &str, max_distance: usize) -> Vec<(String, usize)> {
+        let mut keys: Vec<(String, usize)> = self
+            .map
+            .keys()
+            .map(|key| (key.clone(), levenshtein(key, query)))
+            .filter(|(_, distance)| *distance <= max_distance)
+            .collect();
+        keys.sort_by_key(|(_, distance)| *distance);
+        keys
+    }
+
+    fn scan(&self) -> Vec<(String, String)> {
+        self.map.clone().into_iter().collect()
+    }
+}
+
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, x) in a.chars().enumerate() {
+        let mut next = vec![i + 1];
+        for (j, y) in b.iter().enumerate() {
+            next.push(
+                (row[j] + usize::from(x != *y))
+                    .min(row[j + 1] + 1)
+                    .min(next[j] + 1),
+            );
+        }
+        row = next;
+    }
+    row[b.len()]
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(String, String),
+    DeepDelete(String),
+    DeletePrefix(String),
+    Rename(String, String),
+    RenamePrefix(String, String),
+    SetScore(String, u64),
+    Snapshot,
+}
+
+/// Keys are drawn from a tiny alphabet, so they share prefixes often.
+fn key() -> impl Strategy<Value = String> {
+    "[abŠ]{0,5}"
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => (key(), "[a-z]{0,3}").prop_map(|(k, v)| Op::Insert(k, v)),
+        1 => key().prop_map(Op::DeepDelete),
+        1 => "[abŠ]{1,3}".prop_map(Op::DeletePrefix),
+        1 => (key(), key()).prop_map(|(old, new)| Op::Rename(old, new)),
+        1 => ("[abŠ]{1,3}", "[abŠ]{1,3}").prop_map(|(old, new)| Op::RenamePrefix(old, new)),
+        1 => (key(), 0..5u64).prop_map(|(k, s)| Op::SetScore(k, s)),
+        1 => Just(Op::Snapshot),
+    ]
+}
+
+fn sorted_scan(tree: &CharTree) -> Vec<(String, String)> {
+    let mut scan: Vec<(String, String)> = tree
+        .scan()
+        .into_iter()
+        .map(|(key, value)| (key, value.clone()))
+        .collect();
+    scan.sort();
+    scan
+}
+
+/// Siblings never share a name, every leaf holds a value or history and cached subtree scores are exact.
+fn assert_well_formed(nodes: &[Arc<Node>]) {
+    for (i, node) in nodes.iter().enumerate() {
+        assert!(!nodes[..i].iter().any(|n| n.name == node.name));
+        assert!(!node.is_empty());
+        assert!(node.value.is_some() || node.score == 0);
+        let max_score = node
+            .children
+            .iter()
+            .map(|c| c.max_score)
+            .fold(node.score, u64::max);
+        assert_eq!(node.max_score, max_score);
+        assert_well_formed(&node.children);
+    }
+}
+
+proptest! {
+    #[test]
+    fn prop_matches_model(
+        ops in prop::collection::vec(op(), 0..40),
+        queries in prop::collection::vec(key(), 1..10),
+        versioned: bool,
+    ) {
+        let mut tree = if versioned {
+            CharTree::new_versioned("prop".to_string(), 2)
+        } else {
+            CharTree::new("prop".to_string())
+        };
+        let mut model = Model::default();
+        let mut snapshots = Vec::new();
+
+        for op in ops.iter() {
+            match op {
+                Op::Insert(key, value) => {
+                    tree.insert(key, value);
+                    model.insert(key, value);
+                }
+                Op::DeepDelete(key) => {
+                    tree.deep_delete(key);
+                    model.deep_delete(key);
+                }
+                Op::DeletePrefix(prefix) => {
+                    prop_assert_eq!(tree.delete_prefix(prefix), model.delete_prefix(prefix));
+                }
+                Op::Rename(old, new) => {
+                    prop_assert_eq!(tree.rename(old, new), model.rename(old, new));
+                }
+                Op::RenamePrefix(old, new) => {
+                    prop_assert_eq!(tree.rename_prefix(old, new), model.rename_prefix(old, new));
+                }
+                Op::SetScore(key, score) => {
+                    prop_assert_eq!(tree.set_score(key, *score), model.set_score(key, *score));
+                }
+                Op::Snapshot => snapshots.push((tree.snapshot(), model.scan())),
+            }
+            assert_well_formed(&tree.root);
+        }
+
+        prop_assert_eq!(sorted_scan(&tree), model.scan());
+        for (snapshot, scan) in snapshots.iter() {
+            prop_assert_eq!(&sorted_scan(snapshot), scan);
+            assert_well_formed(&snapshot.root);
+        }
+        for query in queries.iter() {
+            let hit_all: Vec<String> = model.hit_all(query).into_iter().map(|(_, value)| value).collect();
+            prop_assert_eq!(tree.get(query), model.get(query));
+            prop_assert_eq!(tree.hit(query), model.hit(query));
+            prop_assert_eq!(tree.hit_with_key(query), model.hit_with_key(query));
+            prop_assert_eq!(tree.hit_all(query), hit_all);
+            prop_assert_eq!(tree.complete(query, 3), model.complete(query, 3));
+            prop_assert_eq!(tree.count_prefix(query), model.count_prefix(query));
+
+            let mut fuzzy = tree.fuzzy(query, 2);
+            fuzzy.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+            prop_assert_eq!(fuzzy, model.fuzzy(query, 2));
+        }
+    }
+
+    #[test]
+    fn prop_merge_and_diff_match_model(
+        left in prop::collection::vec((key(), "[a-c]"), 0..10),
+        right in prop::collection::vec((key(), "[a-c]"), 0..10),
+        overwrite: bool,
+    ) {
+        let mut tree = CharTree::new("left".to_string());
+        let mut model = BTreeMap::new();
+        for (key, value) in left.iter().filter(|(key, _)| !key.is_empty()) {
+            tree.insert(key, value);
+            model.insert(key.clone(), value.clone());
+        }
+        let mut other = CharTree::new("right".to_string());
+        let mut other_model = BTreeMap::new();
+        for (key, value) in right.iter().filter(|(key, _)| !key.is_empty()) {
+            other.insert(key, value);
+            other_model.insert(key.clone(), value.clone());
+        }
+
+        let mut diff = tree.diff(&other);
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        let added: Vec<String> = other_model.keys().filter(|k| !model.contains_key(*k)).cloned().collect();
+        let removed: Vec<String> = model.keys().filter(|k| !other_model.contains_key(*k)).cloned().collect();
+        let changed: Vec<String> = model
+            .iter()
+            .filter(|(k, v)| other_model.get(*k).is_some_and(|other| other != *v))
+            .map(|(k, _)| k.clone())
+            .collect();
+        prop_assert_eq!(diff, TreeDiff { added, removed, changed });
+
+        let policy = if overwrite { MergePolicy::Overwrite } else { MergePolicy::Keep };
+        let mut written = 0;
+        for (key, value) in other_model {
+            if overwrite || !model.contains_key(&key) {
+                model.insert(key, value);
+                written += 1;
+            }
+        }
+        prop_assert_eq!(tree.merge(&other, policy), written);
+        prop_assert_eq!(sorted_scan(&tree), model.into_iter().collect::<Vec<_>>());
+        assert_well_formed(&tree.root);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn prop_structural_round_trip(
+        ops in prop::collection::vec((key(), "[a-z]{0,3}", 0..3u8), 0..40),
+        versioned: bool,
+    ) {
+        let mut tree = if versioned {
+            CharTree::new_versioned("prop".to_string(), 2)
+        } else {
+            CharTree::new("prop".to_string())
+        };
+        for (key, value, op) in ops.iter() {
+            match op {
+                0 => tree.insert(key, value),
+                1 => tree.deep_delete(key),
+                _ => tree.shallow_delete(key),
+            }
+        }
+
+        let mut encoded = Vec::new();
+        super::structural::serialize(&tree, &mut serde_json::Serializer::new(&mut encoded)).unwrap();
+        let decoded =
+            super::structural::deserialize(&mut serde_json::Deserializer::from_slice(&encoded)).unwrap();
+        prop_assert_eq!(sorted_scan(&decoded), sorted_scan(&tree));
+        assert_well_formed(&decoded.root);
+    }
+
+    #[test]
+    fn prop_hit_is_longest_prefix(keys in prop::collection::vec(key(), 1..10), suffix in key()) {
+        let mut tree = CharTree::new("prop".to_string());
+        for key in keys.iter() {
+            tree.insert(key, key);
+        }
+        for key in keys.iter().filter(|key| !key.is_empty()) {
+            let query = format!("{}{}", key, suffix);
+            let hit = tree.hit(&query).unwrap();
+            prop_assert!(query.starts_with(&hit));
+            prop_assert!(hit.len() >= key.len());
+        }
+    }
+}
@@ -0,0 +1,153 @@
+//! Regex search over the keys of a [`CharTree`].
+//!
+//! The pattern is compiled to a DFA, which is advanced over the UTF-8 bytes of each
+//! node name while walking the tree. As soon as the automaton reaches a dead state
+//! the whole subtree is skipped. The pattern has to match the whole key,
+//! use `.*` on either end to match a part of it.
+
+use std::error::Error;
+use std::fmt;
+
+use log::info;
+use regex_automata::dfa::{dense, Automaton, StartKind};
+use regex_automata::nfa::thompson;
+use regex_automata::util::primitives::StateID;
+use regex_automata::util::start;
+use regex_automata::{Anchored, MatchKind};
+
+use super::{CharTree, Node};
+
+/// Upper bound in bytes for every stage of compiling a pattern, so that
+/// pathological patterns are rejected instead of exhausting memory.
+pub const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+#[derive(Debug, PartialEq)]
+pub struct RegexError(String);
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl From<dense::BuildError> for RegexError {
+    fn from(err: dense::BuildError) -> Self {
+        let mut message = err.to_string();
+        let mut source = err.source();
+        while let Some(err) = source {
+            message.push_str(": ");
+            message.push_str(&err.to_string());
+            source = err.source();
+        }
+        RegexError(message)
+    }
+}
+
+fn build(pattern: &str) -> Result<dense::DFA<Vec<u32>>, RegexError> {
+    let dfa = dense::Builder::new()
+        .configure(
+            dense::Config::new()
+                .start_kind(StartKind::Anchored)
+                // Leftmost-first would stop at "b" for `b|bc`, but every key that matches counts.
+                .match_kind(MatchKind::All)
+                .dfa_size_limit(Some(REGEX_SIZE_LIMIT))
+                .determinize_size_limit(Some(REGEX_SIZE_LIMIT)),
+        )
+        .thompson(thompson::Config::new().nfa_size_limit(Some(REGEX_SIZE_LIMIT)))
+        .build(pattern)?;
+    Ok(dfa)
+}
+
+impl CharTree {
+    /// Returns keys and values where the whole key matches the regex,
+    /// stopping after `limit` matches if one is given.
+    pub fn regex<'a>(
+        &'a self,
+        pattern: &str,
+        limit: Option<usize>,
+    ) -> Result<Vec<(String, &'a String)>, RegexError> {
+        info!("Searching regex {} in tree {}", pattern, &self.name);
+        let dfa = build(pattern)?;
+        let state = dfa
+            .start_state(&start::Config::new().anchored(Anchored::Yes))
+            .map_err(|e| RegexError(e.to_string()))?;
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut result = Vec::new();
+        for node in self.root.iter() {
+            Self::regex_recursive(node, &dfa, state, String::new(), limit, &mut result);
+        }
+        Ok(result)
+    }
+
+    fn regex_recursive<'a>(
+        node: &'a Node,
+        dfa: &dense::DFA<Vec<u32>>,
+        mut state: StateID,
+        mut path: String,
+        limit: usize,
+        result: &mut Vec<(String, &'a String)>,
+    ) {
+        if result.len() >= limit {
+            return;
+        }
+        let mut buffer = [0; 4];
+        for &byte in node.name.encode_utf8(&mut buffer).as_bytes() {
+            state = dfa.next_state(state, byte);
+            if dfa.is_dead_state(state) {
+                return;
+            }
+        }
+        path.push(node.name);
+        if let Some(value) = &node.value {
+            if dfa.is_match_state(dfa.next_eoi_state(state)) {
+                result.push((path.clone(), value));
+            }
+        }
+        for child in node.children.iter() {
+            Self::regex_recursive(child, dfa, state, path.clone(), limit, result);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_tree() -> CharTree {
+        let mut tree = CharTree::new("test".to_string());
+        for key in [
+            "abc", "abd", "axc", "b", "bc", "ŠšŠ", "user1", "user22", "userx",
+        ] {
+            tree.insert(key, &key.to_uppercase());
+        }
+        tree
+    }
+
+    fn keys(tree: &CharTree, pattern: &str, limit: Option<usize>) -> Vec<String> {
+        tree.regex(pattern, limit)
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    #[test]
+    fn test_regex() {
+        let tree = setup_tree();
+        assert_eq!(keys(&tree, "a.c", None), vec!["abc", "axc"]);
+        assert_eq!(keys(&tree, "ab", None), Vec::<String>::new());
+        assert_eq!(keys(&tree, "ab.*", None), vec!["abc", "abd"]);
+        assert_eq!(keys(&tree, r"user\d+", None), vec!["user1", "user22"]);
+        assert_eq!(keys(&tree, "b|bc", None), vec!["b", "bc"]);
+        assert_eq!(keys(&tree, "Š.Š", None), vec!["ŠšŠ"]);
+        assert_eq!(keys(&tree, "(?i)šŠš", None), vec!["ŠšŠ"]);
+        assert_eq!(keys(&tree, ".*", Some(2)), vec!["abc", "abd"]);
+    }
+
+    #[test]
+    fn test_regex_rejects_bad_patterns() {
+        let tree = setup_tree();
+        assert!(tree.regex("a(b", None).is_err());
+        assert!(tree.regex(r"\w{1000}\w{1000}", None).is_err());
+    }
+}
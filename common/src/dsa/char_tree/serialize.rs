@@ -0,0 +1,287 @@
+//! Serde support for [`CharTree`], available with the `serde` feature.
+//!
+//! By default a tree is encoded as its name and a map of every stored key to its value,
+//! which is what you usually want in JSON configs or caches:
+//! ```
+//! # #[cfg(feature = "serde")]
+//! # {
+//! use common::dsa::char_tree::CharTree;
+//!
+//! let mut tree = CharTree::new("routes".to_string());
+//! tree.insert("foo", "bar");
+//! let encoded = serde_json::to_string(&tree).unwrap();
+//! assert_eq!(encoded, r#"{"name":"routes","entries":{"foo":"bar"}}"#);
+//! # }
+//! ```
+//!
+//! If you need to keep the trie shape, use the compact structural encoding
+//! with `#[serde(with = "common::dsa::char_tree::structural")]`.
+//! [`CharTree::save`] and [`CharTree::load`] write and read that encoding with bincode.
+
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{history::Versioning, CharTree, Node};
+
+struct EntriesRef<'a>(&'a CharTree);
+
+impl Serialize for EntriesRef<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.scan())
+    }
+}
+
+/// Keeps entries in the order they were encoded, so the decoded tree scans the same way.
+struct Entries(Vec<(String, String)>);
+
+impl<'de> Deserialize<'de> for Entries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = Entries;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a map of keys to values")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Entries, A::Error> {
+                let mut entries = Vec::with_capacity(map.size_hint().unwrap_or(0));
+                while let Some((key, value)) = map.next_entry::<String, String>()? {
+                    if key.is_empty() {
+                        return Err(de::Error::custom("empty key"));
+                    }
+                    entries.push((key, value));
+                }
+                Ok(Entries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+impl Serialize for CharTree {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("CharTree", 2)?;
+        state.serialize_field("name", &self.name)?;
+        state.serialize_field("entries", &EntriesRef(self))?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for CharTree {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(rename = "CharTree")]
+        struct Repr {
+            name: String,
+            entries: Entries,
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        let mut tree = CharTree::new(repr.name);
+        for (key, value) in repr.entries.0.iter() {
+            tree.insert(key, value);
+        }
+        Ok(tree)
+    }
+}
+
+/// Structural encoding of a [`CharTree`]: every node is stored with its char, value and
+/// children, so the decoded tree has exactly the same shape and child order.
+pub mod structural {
+    use super::*;
+
+    #[derive(Serialize)]
+    #[serde(rename = "CharTree")]
+    struct ReprRef<'a> {
+        name: &'a str,
+        root: &'a [Arc<Node>],
+        versioning: &'a Option<Versioning>,
+    }
+
+    #[derive(Deserialize)]
+    #[serde(rename = "CharTree")]
+    struct Repr {
+        name: String,
+        root: Vec<Arc<Node>>,
+        #[serde(default)]
+        versioning: Option<Versioning>,
+    }
+
+    /// Checks that siblings have distinct names and drops the empty nodes, like the ones
+    /// a shallow delete leaves behind. Cached subtree scores are rebuilt on the way.
+    fn validate(nodes: &mut Vec<Arc<Node>>) -> Result<(), String> {
+        for i in 0..nodes.len() {
+            let (before, rest) = nodes.split_at_mut(i);
+            let node = Arc::make_mut(&mut rest[0]);
+            if before.iter().any(|n| n.name == node.name) {
+                return Err(format!("duplicate node '{}'", node.name));
+            }
+            validate(&mut node.children)?;
+            node.update_max_score();
+        }
+        nodes.retain(|node| !node.is_empty());
+        Ok(())
+    }
+
+    pub fn serialize<S: Serializer>(tree: &CharTree, serializer: S) -> Result<S::Ok, S::Error> {
+        ReprRef {
+            name: &tree.name,
+            root: &tree.root,
+            versioning: &tree.versioning,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<CharTree, D::Error> {
+        let mut repr = Repr::deserialize(deserializer)?;
+        validate(&mut repr.root).map_err(de::Error::custom)?;
+        Ok(CharTree {
+            name: repr.name,
+            root: Arc::new(repr.root),
+            versioning: repr.versioning,
+        })
+    }
+}
+
+impl CharTree {
+    /// Writes the tree in the structural encoding with bincode, e.g. to a file.
+    pub fn save<W: io::Write>(&self, writer: W) -> bincode::Result<()> {
+        let mut serializer = bincode::Serializer::new(writer, bincode::DefaultOptions::new());
+        structural::serialize(self, &mut serializer)
+    }
+
+    /// Reads a tree written by [`CharTree::save`].
+    pub fn load<R: io::Read>(reader: R) -> bincode::Result<CharTree> {
+        let mut deserializer =
+            bincode::Deserializer::with_reader(reader, bincode::DefaultOptions::new());
+        structural::deserialize(&mut deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_tree() -> CharTree {
+        let mut tree = CharTree::new("test".to_string());
+        for (key, value) in [("d", "D"), ("ab", "AB"), ("a", "A"), ("ŠšŠ", "ŪūŪ")] {
+            tree.insert(key, value);
+        }
+        tree
+    }
+
+    fn assert_same(left: &CharTree, right: &CharTree) {
+        assert_eq!(left.name, right.name);
+        assert_eq!(left.scan(), right.scan());
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Cache {
+        #[serde(with = "structural")]
+        tree: CharTree,
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let tree = setup_tree();
+        let encoded = serde_json::to_string(&tree).unwrap();
+        assert_eq!(
+            encoded,
+            r#"{"name":"test","entries":{"d":"D","a":"A","ab":"AB","ŠšŠ":"ŪūŪ"}}"#
+        );
+        assert_same(&tree, &serde_json::from_str(&encoded).unwrap());
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let tree = setup_tree();
+        let encoded = bincode::serialize(&tree).unwrap();
+        assert_same(&tree, &bincode::deserialize(&encoded).unwrap());
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let tree = setup_tree();
+        let encoded = rmp_serde::to_vec(&tree).unwrap();
+        assert_same(&tree, &rmp_serde::from_slice(&encoded).unwrap());
+    }
+
+    #[test]
+    fn test_structural_round_trip() {
+        let cache = Cache { tree: setup_tree() };
+
+        let json = serde_json::to_string(&cache).unwrap();
+        let decoded: Cache = serde_json::from_str(&json).unwrap();
+        assert_same(&cache.tree, &decoded.tree);
+        assert_eq!(json, serde_json::to_string(&decoded).unwrap());
+
+        let encoded = bincode::serialize(&cache).unwrap();
+        let decoded: Cache = bincode::deserialize(&encoded).unwrap();
+        assert_same(&cache.tree, &decoded.tree);
+
+        let encoded = rmp_serde::to_vec(&cache).unwrap();
+        let decoded: Cache = rmp_serde::from_slice(&encoded).unwrap();
+        assert_same(&cache.tree, &decoded.tree);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut tree = setup_tree();
+        tree.set_score("ab", 7);
+        let mut saved = Vec::new();
+        tree.save(&mut saved).unwrap();
+        let loaded = CharTree::load(saved.as_slice()).unwrap();
+        assert_same(&tree, &loaded);
+        assert_eq!(loaded.complete("a", 1), vec!["ab"]);
+        assert!(CharTree::load(&saved[..saved.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_structural_keeps_scores() {
+        let mut cache = Cache { tree: setup_tree() };
+        cache.tree.set_score("ab", 7);
+        let json = serde_json::to_string(&cache).unwrap();
+        let decoded: Cache = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.tree.complete("a", 1), vec!["ab"]);
+    }
+
+    #[test]
+    fn test_structural_keeps_history() {
+        let mut tree = CharTree::new_versioned("test".to_string(), 4);
+        tree.insert("a", "1");
+        tree.deep_delete("a");
+        let json = serde_json::to_string(&Cache { tree }).unwrap();
+        let mut decoded: Cache = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.tree.history("a").len(), 2);
+        decoded.tree.insert("a", "2");
+        assert_eq!(decoded.tree.history("a").last().unwrap().seq, 3);
+    }
+
+    #[test]
+    fn test_structural_rejects_broken_tree() {
+        let duplicate = r#"{"tree":{"name":"t","root":[
+            {"name":"a","value":"A","children":[]},
+            {"name":"a","value":"B","children":[]}]}}"#;
+        assert!(serde_json::from_str::<Cache>(duplicate).is_err());
+    }
+
+    #[test]
+    fn test_structural_drops_empty_nodes() {
+        let mut tree = CharTree::new("test".to_string());
+        tree.insert("a", "1");
+        tree.insert("bc", "2");
+        tree.shallow_delete("a");
+        tree.shallow_delete("bc");
+        let json = serde_json::to_string(&Cache { tree }).unwrap();
+        let decoded: Cache = serde_json::from_str(&json).unwrap();
+        assert!(decoded.tree.root.is_empty());
+    }
+}
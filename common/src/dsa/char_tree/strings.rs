@@ -0,0 +1,138 @@
+//! String operations on the values of a [`CharTree`].
+//!
+//! Offsets and lengths count chars, not bytes, so they never split a multi-byte char.
+//! A missing key behaves like an empty string.
+
+use log::info;
+
+use super::CharTree;
+use crate::dsa::range::resolve_range;
+
+impl CharTree {
+    /// Appends the suffix to the value of the key, creating it if missing, even with
+    /// an empty suffix. Returns the length of the new value.
+    pub fn append(&mut self, key: &str, suffix: &str) -> usize {
+        info!("Appending {} to {}", suffix, key);
+        if !self.has_value(key) {
+            self.insert(key, suffix);
+            return self.strlen(key);
+        }
+        let versioned = self.is_versioned();
+        let value = self
+            .find_node_mut(key)
+            .and_then(|node| node.value.as_mut())
+            .expect("the key has a value");
+        value.push_str(suffix);
+        let len = value.chars().count();
+        if versioned {
+            let value = value.clone();
+            self.record(key, Some(&value));
+        }
+        len
+    }
+
+    /// Returns the length of the value of the key, 0 if the key is missing.
+    pub fn strlen(&self, key: &str) -> usize {
+        self.get(key).map_or(0, |value| value.chars().count())
+    }
+
+    /// Returns the chars of the value between start and end, both inclusive.
+    /// Negative offsets count from the end of the value, -1 being the last char.
+    pub fn get_range(&self, key: &str, start: i64, end: i64) -> String {
+        let value = self.get(key).unwrap_or_default();
+        match resolve_range(value.chars().count(), start, end) {
+            Some((start, end)) => value.chars().skip(start).take(end - start + 1).collect(),
+            None => String::new(),
+        }
+    }
+
+    /// Overwrites the value of the key starting at offset, padding it with `\0`
+    /// if it is shorter than offset. Returns the length of the new value.
+    pub fn set_range(&mut self, key: &str, offset: usize, replacement: &str) -> usize {
+        info!("Setting range of {} at {}", key, offset);
+        let mut chars: Vec<char> = self.get(key).unwrap_or_default().chars().collect();
+        if replacement.is_empty() {
+            return chars.len();
+        }
+        if chars.len() < offset {
+            chars.resize(offset, '\0');
+        }
+        for (i, c) in replacement.chars().enumerate() {
+            match chars.get_mut(offset + i) {
+                Some(existing) => *existing = c,
+                None => chars.push(c),
+            }
+        }
+        self.insert(key, &chars.iter().collect::<String>());
+        chars.len()
+    }
+
+    /// Sets the value of the key and returns the previous one.
+    pub fn get_set(&mut self, key: &str, value: &str) -> Option<String> {
+        let previous = self.get(key);
+        self.insert(key, value);
+        previous
+    }
+
+    /// Removes the key and returns its value.
+    pub fn get_del(&mut self, key: &str) -> Option<String> {
+        let value = self.get(key)?;
+        self.deep_delete(key);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_and_strlen() {
+        let mut tree = CharTree::new("test".to_string());
+        assert_eq!(tree.append("log", "ab"), 2);
+        assert_eq!(tree.append("log", "cé"), 4);
+        assert_eq!(tree.get("log").unwrap(), "abcé".to_string());
+        assert_eq!(tree.strlen("log"), 4);
+        assert_eq!(tree.strlen("missing"), 0);
+        assert_eq!(tree.append("empty", ""), 0);
+        assert_eq!(tree.get("empty"), Some(String::new()));
+
+        let mut tree = CharTree::new_versioned("test".to_string(), 4);
+        tree.append("log", "a");
+        tree.append("log", "b");
+        let history: Vec<Option<String>> =
+            tree.history("log").into_iter().map(|v| v.value).collect();
+        assert_eq!(history, vec![Some("a".to_string()), Some("ab".to_string())]);
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut tree = CharTree::new("test".to_string());
+        tree.insert("greeting", "Hello World");
+        assert_eq!(tree.get_range("greeting", 0, 4), "Hello".to_string());
+        assert_eq!(tree.get_range("greeting", -5, -1), "World".to_string());
+        assert_eq!(tree.get_range("greeting", 6, 100), "World".to_string());
+        assert_eq!(tree.get_range("greeting", 5, 2), String::new());
+        assert_eq!(tree.get_range("missing", 0, -1), String::new());
+
+        assert_eq!(tree.set_range("greeting", 6, "Rust!"), 11);
+        assert_eq!(tree.get("greeting").unwrap(), "Hello Rust!".to_string());
+        assert_eq!(tree.set_range("greeting", 13, "x"), 14);
+        assert_eq!(
+            tree.get("greeting").unwrap(),
+            "Hello Rust!\0\0x".to_string()
+        );
+        assert_eq!(tree.set_range("missing", 0, ""), 0);
+        assert_eq!(tree.get("missing"), None);
+    }
+
+    #[test]
+    fn test_get_set_and_get_del() {
+        let mut tree = CharTree::new("test".to_string());
+        assert_eq!(tree.get_set("a", "1"), None);
+        assert_eq!(tree.get_set("a", "2"), Some("1".to_string()));
+        assert_eq!(tree.get_del("a"), Some("2".to_string()));
+        assert_eq!(tree.get_del("a"), None);
+        assert!(tree.scan().is_empty());
+    }
+}
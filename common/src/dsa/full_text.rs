@@ -0,0 +1,561 @@
+//! This module provides an inverted full-text index - structure that finds documents by the
+//! words in their text, ranked by relevance with BM25.
+//! Example:
+//! ```
+//! use common::dsa::full_text::FullTextIndex;
+//!
+//! let mut index = FullTextIndex::new("index_name".to_string(), true);
+//! index.add("1", "The quick brown fox jumps over the lazy dog");
+//! index.add("2", "Quick thinking, quicker jumping");
+//! index.add("3", "A brown dog sleeps");
+//!
+//! let ids = |query| -> Vec<String> {
+//!     let results = index.search(query).unwrap();
+//!     results.into_iter().map(|(id, _)| id.to_string()).collect()
+//! };
+//! assert_eq!(ids("jumping"), vec!["2", "1"]);
+//! assert_eq!(ids("brown AND NOT fox"), vec!["3"]);
+//! assert_eq!(ids("\"lazy dog\" OR sleeps"), vec!["3", "1"]);
+//! ```
+//!
+//! Text is split on Unicode word boundaries and lowercased, and with stemming each word is
+//! reduced to its English stem, so "jumps" and "jumping" match. Queries combine words and
+//! quoted phrases with AND (implied between operands), OR, NOT and parentheses.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+
+use log::info;
+use rust_stemmers::{Algorithm, Stemmer};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// BM25 term frequency saturation.
+const K1: f64 = 1.2;
+/// BM25 document length normalization.
+const B: f64 = 0.75;
+/// Upper bound for the words, phrases, operators and parentheses of a query, which also
+/// bounds the depth of its evaluation.
+pub const MAX_QUERY_TOKENS: usize = 256;
+
+#[derive(Debug, PartialEq)]
+pub enum FullTextError {
+    EmptyQuery,
+    UnclosedQuote,
+    UnbalancedParentheses,
+    /// An operator is missing a word, phrase or group to apply to.
+    MissingOperand,
+    TooLong,
+}
+
+impl fmt::Display for FullTextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FullTextError::EmptyQuery => write!(f, "query has no words"),
+            FullTextError::UnclosedQuote => write!(f, "query has an unclosed quote"),
+            FullTextError::UnbalancedParentheses => {
+                write!(f, "query has unbalanced parentheses")
+            }
+            FullTextError::MissingOperand => write!(f, "query has an operator without operand"),
+            FullTextError::TooLong => {
+                write!(f, "query has more than {} tokens", MAX_QUERY_TOKENS)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Phrase(String),
+    Open,
+    Close,
+    And,
+    Or,
+    Not,
+}
+
+fn lex(query: &str) -> Result<Vec<Token>, FullTextError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => tokens.push(Token::Open),
+            ')' => tokens.push(Token::Close),
+            '"' => {
+                let mut phrase = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => phrase.push(c),
+                        None => return Err(FullTextError::UnclosedQuote),
+                    }
+                }
+                tokens.push(Token::Phrase(phrase));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut word = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || matches!(c, '(' | ')' | '"') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(match word.as_str() {
+                    "AND" => Token::And,
+                    "OR" => Token::Or,
+                    "NOT" => Token::Not,
+                    _ => Token::Word(word),
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Debug, PartialEq)]
+enum Query {
+    /// Terms that must follow each other. A single word of the query is a phrase of one term,
+    /// or of more if the tokenizer splits it.
+    Phrase(Vec<String>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    /// Terms that make a document match, rather than exclude it. They are the ones scored.
+    fn positive_terms<'a>(&'a self, terms: &mut BTreeSet<&'a str>) {
+        match self {
+            Query::Phrase(phrase) => terms.extend(phrase.iter().map(String::as_str)),
+            Query::And(left, right) | Query::Or(left, right) => {
+                left.positive_terms(terms);
+                right.positive_terms(terms);
+            }
+            Query::Not(_) => {}
+        }
+    }
+}
+
+/// Recursive descent parser of: or := and (OR and)*, and := unary (AND? unary)*,
+/// unary := NOT unary | ( or ) | word | phrase.
+/// Operands without any term, like a word of punctuation only, are skipped, so the parsers
+/// return None when nothing is left.
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    index: &'a FullTextIndex,
+}
+
+/// Joins two operands with the operator, or returns the one left if the other was skipped.
+fn join(
+    left: Option<Query>,
+    right: Option<Query>,
+    operator: fn(Box<Query>, Box<Query>) -> Query,
+) -> Option<Query> {
+    match (left, right) {
+        (Some(left), Some(right)) => Some(operator(Box::new(left), Box::new(right))),
+        (left, right) => left.or(right),
+    }
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn parse_or(&mut self) -> Result<Option<Query>, FullTextError> {
+        let mut query = self.parse_and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.position += 1;
+            query = join(query, self.parse_and()?, Query::Or);
+        }
+        Ok(query)
+    }
+
+    fn parse_and(&mut self) -> Result<Option<Query>, FullTextError> {
+        let mut query = self.parse_unary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => self.position += 1,
+                Some(Token::Or) | Some(Token::Close) | None => return Ok(query),
+                _ => {}
+            }
+            query = join(query, self.parse_unary()?, Query::And);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Option<Query>, FullTextError> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        match token {
+            Some(Token::Not) => Ok(self.parse_unary()?.map(|query| Query::Not(Box::new(query)))),
+            Some(Token::Open) => {
+                let query = self.parse_or()?;
+                if self.peek() != Some(&Token::Close) {
+                    return Err(FullTextError::UnbalancedParentheses);
+                }
+                self.position += 1;
+                Ok(query)
+            }
+            Some(Token::Word(text)) | Some(Token::Phrase(text)) => {
+                let phrase = self.index.tokenize(text);
+                Ok((!phrase.is_empty()).then_some(Query::Phrase(phrase)))
+            }
+            Some(Token::Close) => Err(FullTextError::UnbalancedParentheses),
+            Some(Token::And) | Some(Token::Or) | None => Err(FullTextError::MissingOperand),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Document {
+    /// Number of terms.
+    length: usize,
+    /// Distinct terms, to remove the document from their postings.
+    terms: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FullTextIndex {
+    pub name: String,
+    stemming: bool,
+    /// Positions of every term in the documents that contain it, by document id.
+    postings: HashMap<String, BTreeMap<String, Vec<usize>>>,
+    documents: HashMap<String, Document>,
+    /// Sum of the lengths of the documents.
+    total_length: usize,
+}
+
+impl FullTextIndex {
+    /// Creates an index. With stemming, words are reduced to their English stem.
+    pub fn new(name: String, stemming: bool) -> Self {
+        info!(
+            "Creating new full-text index with name: {}, stemming: {}",
+            name, stemming
+        );
+        FullTextIndex {
+            name,
+            stemming,
+            postings: HashMap::new(),
+            documents: HashMap::new(),
+            total_length: 0,
+        }
+    }
+
+    pub fn is_stemming(&self) -> bool {
+        self.stemming
+    }
+
+    /// Splits the text into lowercase terms, stemmed if the index is stemming.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        let words = text.unicode_words().map(str::to_lowercase);
+        if !self.stemming {
+            return words.collect();
+        }
+        let stemmer = Stemmer::create(Algorithm::English);
+        words.map(|word| stemmer.stem(&word).into_owned()).collect()
+    }
+
+    /// Indexes the text of the document, replacing its previous text.
+    /// Returns false if the document was already indexed.
+    pub fn add(&mut self, id: &str, text: &str) -> bool {
+        let replaced = self.remove(id);
+        info!("Indexing document {} in full-text index {}", id, self.name);
+        let terms = self.tokenize(text);
+        let mut positions: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+        for (position, term) in terms.iter().enumerate() {
+            positions.entry(term.clone()).or_default().push(position);
+        }
+        self.documents.insert(
+            id.to_string(),
+            Document {
+                length: terms.len(),
+                terms: positions.keys().cloned().collect(),
+            },
+        );
+        self.total_length += terms.len();
+        for (term, positions) in positions {
+            self.postings
+                .entry(term)
+                .or_default()
+                .insert(id.to_string(), positions);
+        }
+        !replaced
+    }
+
+    /// Removes the document. Returns false if it was not indexed.
+    pub fn remove(&mut self, id: &str) -> bool {
+        let document = match self.documents.remove(id) {
+            Some(document) => document,
+            None => return false,
+        };
+        info!(
+            "Removing document {} from full-text index {}",
+            id, self.name
+        );
+        self.total_length -= document.length;
+        for term in document.terms {
+            if let Some(documents) = self.postings.get_mut(&term) {
+                documents.remove(id);
+                if documents.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.documents.contains_key(id)
+    }
+
+    /// Returns true if the terms follow each other somewhere in the document.
+    fn contains_phrase(&self, id: &str, phrase: &[String]) -> bool {
+        let positions: Option<Vec<&Vec<usize>>> = phrase
+            .iter()
+            .map(|term| {
+                self.postings
+                    .get(term)
+                    .and_then(|documents| documents.get(id))
+            })
+            .collect();
+        let positions = match positions {
+            Some(positions) => positions,
+            None => return false,
+        };
+        positions[0].iter().any(|start| {
+            positions
+                .iter()
+                .enumerate()
+                .skip(1)
+                .all(|(offset, term)| term.binary_search(&(start + offset)).is_ok())
+        })
+    }
+
+    fn matches(&self, query: &Query) -> BTreeSet<&str> {
+        match query {
+            Query::Phrase(phrase) => {
+                let candidates = match phrase.first().and_then(|term| self.postings.get(term)) {
+                    Some(documents) => documents.keys(),
+                    None => return BTreeSet::new(),
+                };
+                candidates
+                    .map(String::as_str)
+                    .filter(|id| phrase.len() == 1 || self.contains_phrase(id, phrase))
+                    .collect()
+            }
+            Query::And(left, right) => {
+                let left = self.matches(left);
+                let right = self.matches(right);
+                left.intersection(&right).copied().collect()
+            }
+            Query::Or(left, right) => {
+                let mut left = self.matches(left);
+                left.extend(self.matches(right));
+                left
+            }
+            Query::Not(query) => {
+                let excluded = self.matches(query);
+                self.documents
+                    .keys()
+                    .map(String::as_str)
+                    .filter(|id| !excluded.contains(id))
+                    .collect()
+            }
+        }
+    }
+
+    /// BM25 score of the document for the terms.
+    fn score(&self, id: &str, terms: &BTreeSet<&str>) -> f64 {
+        let count = self.documents.len() as f64;
+        let average_length = self.total_length as f64 / count;
+        let length = self.documents[id].length as f64;
+        terms
+            .iter()
+            .filter_map(|term| self.postings.get(*term))
+            .map(|documents| {
+                let frequency = documents.get(id).map_or(0, Vec::len) as f64;
+                let with_term = documents.len() as f64;
+                let idf = ((count - with_term + 0.5) / (with_term + 0.5) + 1.0).ln();
+                let normalization = 1.0 - B + B * length / average_length.max(1.0);
+                idf * frequency * (K1 + 1.0) / (frequency + K1 * normalization)
+            })
+            .sum()
+    }
+
+    /// Returns the ids of the documents matching the query with their score, best first.
+    pub fn search(&self, query: &str) -> Result<Vec<(&str, f64)>, FullTextError> {
+        let mut parser = Parser {
+            tokens: lex(query)?,
+            position: 0,
+            index: self,
+        };
+        if parser.tokens.is_empty() {
+            return Err(FullTextError::EmptyQuery);
+        }
+        if parser.tokens.len() > MAX_QUERY_TOKENS {
+            return Err(FullTextError::TooLong);
+        }
+        let query = parser.parse_or()?;
+        if parser.position < parser.tokens.len() {
+            return Err(FullTextError::UnbalancedParentheses);
+        }
+        let query = query.ok_or(FullTextError::EmptyQuery)?;
+
+        let mut terms = BTreeSet::new();
+        query.positive_terms(&mut terms);
+        let mut results: Vec<(&str, f64)> = self
+            .matches(&query)
+            .into_iter()
+            .map(|id| (id, self.score(id, &terms)))
+            .collect();
+        results.sort_by(|(a_id, a_score), (b_id, b_score)| {
+            b_score.total_cmp(a_score).then_with(|| a_id.cmp(b_id))
+        });
+        Ok(results)
+    }
+
+    /// Number of documents.
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_index(stemming: bool) -> FullTextIndex {
+        let mut index = FullTextIndex::new("test".to_string(), stemming);
+        index.add("a", "Rust is a systems programming language");
+        index.add("b", "Programming in Rust: systems, safety and speed");
+        index.add("c", "The Python programming language");
+        index.add("d", "Café crème, s'il vous plaît");
+        index
+    }
+
+    fn ids(index: &FullTextIndex, query: &str) -> Vec<String> {
+        let results = index.search(query).unwrap();
+        results.into_iter().map(|(id, _)| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_tokenize() {
+        let index = FullTextIndex::new("test".to_string(), false);
+        assert_eq!(
+            index.tokenize("Hello, Wörld! It's 3.5 déjà-vu"),
+            vec!["hello", "wörld", "it's", "3.5", "déjà", "vu"]
+        );
+        let index = FullTextIndex::new("test".to_string(), true);
+        assert_eq!(
+            index.tokenize("Running runners ran"),
+            vec!["run", "runner", "ran"]
+        );
+    }
+
+    #[test]
+    fn test_boolean_queries() {
+        let index = setup_index(false);
+        assert_eq!(ids(&index, "programming language"), vec!["c", "a"]);
+        assert_eq!(ids(&index, "rust AND NOT language"), vec!["b"]);
+        assert_eq!(ids(&index, "python OR CAFÉ"), vec!["c", "d"]);
+        assert_eq!(ids(&index, "(python OR speed) language"), vec!["c"]);
+        assert_eq!(ids(&index, "NOT programming"), vec!["d"]);
+        assert!(ids(&index, "java").is_empty());
+    }
+
+    #[test]
+    fn test_operands_without_terms_are_skipped() {
+        let index = setup_index(false);
+        assert_eq!(ids(&index, "rust &"), ids(&index, "rust"));
+        assert_eq!(ids(&index, "rust AND - AND safety"), vec!["b"]);
+        assert_eq!(ids(&index, "python OR (&) OR NOT ..."), vec!["c"]);
+        assert_eq!(ids(&index, "\"!\" café"), vec!["d"]);
+    }
+
+    #[test]
+    fn test_phrase_queries() {
+        let index = setup_index(false);
+        assert_eq!(ids(&index, "\"programming language\""), vec!["c", "a"]);
+        assert_eq!(
+            ids(&index, "\"language programming\""),
+            Vec::<String>::new()
+        );
+        // Punctuation between words doesn't break a phrase.
+        assert_eq!(ids(&index, "\"rust systems\""), vec!["b"]);
+        assert_eq!(ids(&index, "\"in Rust\" OR \"crème s'il\""), vec!["d", "b"]);
+    }
+
+    #[test]
+    fn test_ranking() {
+        let mut index = FullTextIndex::new("test".to_string(), true);
+        index.add("short", "database");
+        index.add("long", "a database among many other words in a long text");
+        index.add("repeated", "database databases and more databases");
+        index.add("other", "nothing relevant");
+        let results = index.search("database").unwrap();
+        assert_eq!(results[0].0, "repeated");
+        assert_eq!(results[1].0, "short");
+        assert_eq!(results[2].0, "long");
+        assert!(results[2].1 > 0.0);
+    }
+
+    #[test]
+    fn test_remove_and_replace() {
+        let mut index = setup_index(false);
+        assert!(index.remove("a"));
+        assert!(!index.remove("a"));
+        assert_eq!(ids(&index, "rust"), vec!["b"]);
+        assert!(!index.add("b", "Go programming"));
+        assert!(ids(&index, "rust").is_empty());
+        assert_eq!(ids(&index, "go"), vec!["b"]);
+        assert_eq!(index.len(), 3);
+        assert!(!index.postings.contains_key("rust"));
+    }
+
+    #[test]
+    fn test_invalid_queries() {
+        let index = setup_index(false);
+        assert_eq!(index.search("  ").unwrap_err(), FullTextError::EmptyQuery);
+        assert_eq!(
+            index.search("& \"--\"").unwrap_err(),
+            FullTextError::EmptyQuery
+        );
+        assert_eq!(
+            index.search("NOT (&)").unwrap_err(),
+            FullTextError::EmptyQuery
+        );
+        assert_eq!(
+            index.search("\"rust").unwrap_err(),
+            FullTextError::UnclosedQuote
+        );
+        assert_eq!(
+            index.search("(rust").unwrap_err(),
+            FullTextError::UnbalancedParentheses
+        );
+        assert_eq!(
+            index.search("rust)").unwrap_err(),
+            FullTextError::UnbalancedParentheses
+        );
+        assert_eq!(
+            index.search("rust OR").unwrap_err(),
+            FullTextError::MissingOperand
+        );
+        assert_eq!(
+            index.search("NOT").unwrap_err(),
+            FullTextError::MissingOperand
+        );
+        assert_eq!(
+            index.search(&"(".repeat(100_000)).unwrap_err(),
+            FullTextError::TooLong
+        );
+    }
+}
@@ -0,0 +1,459 @@
+//! This module provides a geospatial index - structure of members located by longitude and
+//! latitude, searchable by distance.
+//! Example:
+//! ```
+//! use common::dsa::geo::{GeoIndex, Shape};
+//!
+//! let mut stores = GeoIndex::new("stores".to_string());
+//! stores.add("vilnius", 25.2797, 54.6872).unwrap();
+//! stores.add("kaunas", 23.9036, 54.8985).unwrap();
+//! stores.add("riga", 24.1052, 56.9496).unwrap();
+//!
+//! let distance = stores.distance("vilnius", "kaunas").unwrap();
+//! assert!((distance - 92_000.0).abs() < 1_000.0);
+//!
+//! let near = stores.search((25.0, 54.8), Shape::Radius(150_000.0)).unwrap();
+//! let names: Vec<&str> = near.iter().map(|(name, _)| name.as_str()).collect();
+//! assert_eq!(names, vec!["vilnius", "kaunas"]);
+//! ```
+//!
+//! Like in Redis, members are kept in a sorted set scored by a 52 bits geohash, which
+//! interleaves the bits of the longitude and latitude cells so nearby members get close scores.
+//! A search reads the score ranges of the few cells covering the area, then filters the
+//! members by their distance on the sphere. Positions are precise to about a meter.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use log::info;
+
+use super::sorted_set::SortedSet;
+
+/// Bits of the longitude and of the latitude in a geohash.
+const STEP: u32 = 26;
+const LONGITUDE_MIN: f64 = -180.0;
+const LONGITUDE_MAX: f64 = 180.0;
+/// Latitudes of the Web Mercator projection, beyond which maps don't go.
+const LATITUDE_MIN: f64 = -85.05112878;
+const LATITUDE_MAX: f64 = 85.05112878;
+/// Earth radius in meters, as used by Redis, so distances match.
+const EARTH_RADIUS: f64 = 6372797.560856;
+
+#[derive(Debug, PartialEq)]
+pub enum GeoError {
+    InvalidPosition {
+        longitude: f64,
+        latitude: f64,
+    },
+    /// Radius, width and height must be positive numbers or zero.
+    InvalidShape,
+}
+
+impl fmt::Display for GeoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GeoError::InvalidPosition {
+                longitude,
+                latitude,
+            } => write!(
+                f,
+                "invalid longitude,latitude pair {},{}",
+                longitude, latitude
+            ),
+            GeoError::InvalidShape => write!(f, "radius, width and height must be positive"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Meters,
+    Kilometers,
+    Miles,
+    Feet,
+}
+
+impl Unit {
+    /// Number of meters in one unit.
+    pub fn meters(self) -> f64 {
+        match self {
+            Unit::Meters => 1.0,
+            Unit::Kilometers => 1000.0,
+            Unit::Miles => 1609.34,
+            Unit::Feet => 0.3048,
+        }
+    }
+}
+
+/// Area of a search around a center, in meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Radius(f64),
+    /// Width along the parallel and height along the meridian of the center.
+    Box {
+        width: f64,
+        height: f64,
+    },
+}
+
+/// Spreads the 32 bits of x to the even bits of the result.
+fn spread(x: u32) -> u64 {
+    let mut x = u64::from(x);
+    x = (x | (x << 16)) & 0x0000_FFFF_0000_FFFF;
+    x = (x | (x << 8)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x << 4)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x << 2)) & 0x3333_3333_3333_3333;
+    (x | (x << 1)) & 0x5555_5555_5555_5555
+}
+
+/// Inverse of [`spread`], gathering the even bits of x.
+fn squash(x: u64) -> u32 {
+    let mut x = x & 0x5555_5555_5555_5555;
+    x = (x | (x >> 1)) & 0x3333_3333_3333_3333;
+    x = (x | (x >> 2)) & 0x0F0F_0F0F_0F0F_0F0F;
+    x = (x | (x >> 4)) & 0x00FF_00FF_00FF_00FF;
+    x = (x | (x >> 8)) & 0x0000_FFFF_0000_FFFF;
+    ((x | (x >> 16)) & 0x0000_0000_FFFF_FFFF) as u32
+}
+
+/// Index of the cell holding value, when the range from min to max is cut in 2^step cells.
+fn cell(value: f64, min: f64, max: f64, step: u32) -> u32 {
+    let cells = f64::from(1u32 << step);
+    let index = ((value - min) / (max - min) * cells).floor();
+    index.clamp(0.0, cells - 1.0) as u32
+}
+
+/// Geohash of the cells at some step: latitude bits are the even ones, longitude bits the odd.
+fn geohash(longitude_cell: u32, latitude_cell: u32) -> u64 {
+    spread(latitude_cell) | (spread(longitude_cell) << 1)
+}
+
+fn encode(longitude: f64, latitude: f64) -> u64 {
+    geohash(
+        cell(longitude, LONGITUDE_MIN, LONGITUDE_MAX, STEP),
+        cell(latitude, LATITUDE_MIN, LATITUDE_MAX, STEP),
+    )
+}
+
+/// Returns the center of the cell of the geohash.
+fn decode(hash: u64) -> (f64, f64) {
+    let cells = f64::from(1u32 << STEP);
+    let center =
+        |index: u32, min: f64, max: f64| min + (f64::from(index) + 0.5) * (max - min) / cells;
+    (
+        center(squash(hash >> 1), LONGITUDE_MIN, LONGITUDE_MAX),
+        center(squash(hash), LATITUDE_MIN, LATITUDE_MAX),
+    )
+}
+
+/// Great-circle distance in meters between two (longitude, latitude) positions.
+pub fn haversine(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (longitude_from, latitude_from) = (from.0.to_radians(), from.1.to_radians());
+    let (longitude_to, latitude_to) = (to.0.to_radians(), to.1.to_radians());
+    let u = ((latitude_to - latitude_from) / 2.0).sin();
+    let v = ((longitude_to - longitude_from) / 2.0).sin();
+    let a = u * u + latitude_from.cos() * latitude_to.cos() * v * v;
+    2.0 * EARTH_RADIUS * a.sqrt().asin()
+}
+
+fn check_position(longitude: f64, latitude: f64) -> Result<(), GeoError> {
+    if (LONGITUDE_MIN..=LONGITUDE_MAX).contains(&longitude)
+        && (LATITUDE_MIN..=LATITUDE_MAX).contains(&latitude)
+    {
+        return Ok(());
+    }
+    Err(GeoError::InvalidPosition {
+        longitude,
+        latitude,
+    })
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeoIndex {
+    pub name: String,
+    members: SortedSet,
+}
+
+impl GeoIndex {
+    pub fn new(name: String) -> Self {
+        info!("Creating new geo index with name: {}", name);
+        GeoIndex {
+            members: SortedSet::new(name.clone()),
+            name,
+        }
+    }
+
+    /// Adds the member or moves it. Returns true if the member is new.
+    pub fn add(&mut self, member: &str, longitude: f64, latitude: f64) -> Result<bool, GeoError> {
+        check_position(longitude, latitude)?;
+        let score = encode(longitude, latitude) as f64;
+        Ok(self
+            .members
+            .add(member, score)
+            .expect("a geohash is a number"))
+    }
+
+    /// Removes the member. Returns false if it was not present.
+    pub fn remove(&mut self, member: &str) -> bool {
+        self.members.remove(member)
+    }
+
+    /// Returns the (longitude, latitude) of the member.
+    pub fn position(&self, member: &str) -> Option<(f64, f64)> {
+        self.members.score(member).map(|score| decode(score as u64))
+    }
+
+    /// Returns the distance between the members in meters.
+    pub fn distance(&self, from: &str, to: &str) -> Option<f64> {
+        Some(haversine(self.position(from)?, self.position(to)?))
+    }
+
+    /// Returns the members in the cells covering the area of the given size around the center.
+    fn candidates(&self, center: (f64, f64), width: f64, height: f64) -> Vec<(String, f64)> {
+        let (longitude, latitude) = center;
+        let latitude_delta = (height / 2.0 / EARTH_RADIUS).to_degrees();
+        let latitude_min = (latitude - latitude_delta).max(LATITUDE_MIN);
+        let latitude_max = (latitude + latitude_delta).min(LATITUDE_MAX);
+        // Parallels shrink away from the equator, so the widest longitude span is at the
+        // latitude farthest from it.
+        let farthest = latitude_min.abs().max(latitude_max.abs()).to_radians();
+        let longitude_delta = (width / 2.0 / (EARTH_RADIUS * farthest.cos())).to_degrees();
+        let longitude_ranges = if longitude_delta >= 180.0 {
+            vec![(LONGITUDE_MIN, LONGITUDE_MAX)]
+        } else if longitude - longitude_delta < LONGITUDE_MIN {
+            vec![
+                (longitude - longitude_delta + 360.0, LONGITUDE_MAX),
+                (LONGITUDE_MIN, longitude + longitude_delta),
+            ]
+        } else if longitude + longitude_delta > LONGITUDE_MAX {
+            vec![
+                (longitude - longitude_delta, LONGITUDE_MAX),
+                (LONGITUDE_MIN, longitude + longitude_delta - 360.0),
+            ]
+        } else {
+            vec![(longitude - longitude_delta, longitude + longitude_delta)]
+        };
+
+        // The largest cells at least as big as the area, so it covers at most 2 per axis.
+        let mut step = STEP;
+        while step > 0 {
+            let cells = f64::from(1u32 << step);
+            let longitude_span = longitude_delta.min(180.0) * 2.0;
+            if (LATITUDE_MAX - LATITUDE_MIN) / cells >= latitude_max - latitude_min
+                && (LONGITUDE_MAX - LONGITUDE_MIN) / cells >= longitude_span
+            {
+                break;
+            }
+            step -= 1;
+        }
+
+        let shift = 2 * (STEP - step);
+        let mut candidates = BTreeMap::new();
+        let latitude_cells = cell(latitude_min, LATITUDE_MIN, LATITUDE_MAX, step)
+            ..=cell(latitude_max, LATITUDE_MIN, LATITUDE_MAX, step);
+        for latitude_cell in latitude_cells {
+            for (from, to) in &longitude_ranges {
+                let longitude_cells = cell(*from, LONGITUDE_MIN, LONGITUDE_MAX, step)
+                    ..=cell(*to, LONGITUDE_MIN, LONGITUDE_MAX, step);
+                for longitude_cell in longitude_cells {
+                    let hash = geohash(longitude_cell, latitude_cell);
+                    let min = hash << shift;
+                    let max = ((hash + 1) << shift) - 1;
+                    candidates.extend(self.members.range_by_score(min as f64, max as f64));
+                }
+            }
+        }
+        candidates.into_iter().collect()
+    }
+
+    /// Returns the members within the shape around the center, with their distance to it in
+    /// meters, closest first.
+    pub fn search(&self, center: (f64, f64), shape: Shape) -> Result<Vec<(String, f64)>, GeoError> {
+        check_position(center.0, center.1)?;
+        let (width, height) = match shape {
+            Shape::Radius(radius) => (radius * 2.0, radius * 2.0),
+            Shape::Box { width, height } => (width, height),
+        };
+        if !(width >= 0.0 && height >= 0.0 && width.is_finite() && height.is_finite()) {
+            return Err(GeoError::InvalidShape);
+        }
+
+        let mut results: Vec<(String, f64)> = self
+            .candidates(center, width, height)
+            .into_iter()
+            .filter_map(|(member, score)| {
+                let position = decode(score as u64);
+                let distance = haversine(center, position);
+                let inside = match shape {
+                    Shape::Radius(radius) => distance <= radius,
+                    Shape::Box { width, height } => {
+                        haversine((center.0, position.1), center) <= height / 2.0
+                            && haversine((center.0, position.1), position) <= width / 2.0
+                    }
+                };
+                inside.then_some((member, distance))
+            })
+            .collect();
+        results.sort_by(|(a_member, a_distance), (b_member, b_distance)| {
+            a_distance
+                .total_cmp(b_distance)
+                .then_with(|| a_member.cmp(b_member))
+        });
+        Ok(results)
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_index() -> GeoIndex {
+        let mut index = GeoIndex::new("test".to_string());
+        for (member, longitude, latitude) in [
+            ("palermo", 13.361389, 38.115556),
+            ("catania", 15.087269, 37.502669),
+            ("rome", 12.496366, 41.902782),
+            ("fiji-east", 179.9, -17.8),
+            ("fiji-west", -179.9, -17.8),
+        ] {
+            index.add(member, longitude, latitude).unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn test_encoding() {
+        for (longitude, latitude) in [
+            (0.0, 0.0),
+            (-180.0, -85.05),
+            (179.999, 85.05),
+            (13.36, 38.11),
+        ] {
+            let (decoded_longitude, decoded_latitude) = decode(encode(longitude, latitude));
+            assert!((decoded_longitude - longitude).abs() < 1e-5);
+            assert!((decoded_latitude - latitude).abs() < 1e-5);
+        }
+        assert!(encode(180.0, 85.05112878) < 1 << 52);
+        assert_eq!(squash(spread(0xDEAD_BEEF)), 0xDEAD_BEEF);
+    }
+
+    #[test]
+    fn test_add_and_distance() {
+        let mut index = setup_index();
+        assert_eq!(
+            index.add("pole", 0.0, 90.0),
+            Err(GeoError::InvalidPosition {
+                longitude: 0.0,
+                latitude: 90.0
+            })
+        );
+        let (longitude, latitude) = index.position("palermo").unwrap();
+        assert!((longitude - 13.361389).abs() < 1e-5);
+        assert!((latitude - 38.115556).abs() < 1e-5);
+        // Redis returns 166274.1516 meters for the same pair.
+        let distance = index.distance("palermo", "catania").unwrap();
+        assert!((distance - 166_274.15).abs() < 1.0, "{}", distance);
+        assert_eq!(index.distance("palermo", "missing"), None);
+
+        assert!(!index.add("palermo", 13.0, 38.0).unwrap());
+        assert!(index.remove("palermo"));
+        assert_eq!(index.len(), 4);
+    }
+
+    #[test]
+    fn test_search_radius() {
+        let index = setup_index();
+        let names = |results: Vec<(String, f64)>| -> Vec<String> {
+            results.into_iter().map(|(member, _)| member).collect()
+        };
+        let center = (15.0, 37.0);
+        assert_eq!(
+            names(index.search(center, Shape::Radius(200_000.0)).unwrap()),
+            vec!["catania", "palermo"]
+        );
+        assert_eq!(
+            names(index.search(center, Shape::Radius(100_000.0)).unwrap()),
+            vec!["catania"]
+        );
+        assert_eq!(
+            names(index.search(center, Shape::Radius(1_000_000.0)).unwrap()),
+            vec!["catania", "palermo", "rome"]
+        );
+        // Across the antimeridian.
+        let mut fiji = names(
+            index
+                .search((180.0, -17.8), Shape::Radius(50_000.0))
+                .unwrap(),
+        );
+        fiji.sort();
+        assert_eq!(fiji, vec!["fiji-east", "fiji-west"]);
+        assert_eq!(
+            index.search(center, Shape::Radius(-1.0)),
+            Err(GeoError::InvalidShape)
+        );
+    }
+
+    #[test]
+    fn test_search_box() {
+        let index = setup_index();
+        let results = index
+            .search(
+                (15.0, 37.0),
+                Shape::Box {
+                    width: 400_000.0,
+                    height: 400_000.0,
+                },
+            )
+            .unwrap();
+        let names: Vec<&str> = results.iter().map(|(member, _)| member.as_str()).collect();
+        assert_eq!(names, vec!["catania", "palermo"]);
+        let results = index
+            .search(
+                (15.0, 37.0),
+                Shape::Box {
+                    width: 400_000.0,
+                    height: 150_000.0,
+                },
+            )
+            .unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn test_search_matches_brute_force() {
+        let mut index = GeoIndex::new("test".to_string());
+        let mut positions = Vec::new();
+        for i in 0..2000u32 {
+            let longitude = f64::from(i * 7919 % 3600) / 10.0 - 180.0;
+            let latitude = f64::from(i * 104_729 % 1700) / 10.0 - 85.0;
+            index.add(&i.to_string(), longitude, latitude).unwrap();
+            positions.push((i.to_string(), index.position(&i.to_string()).unwrap()));
+        }
+        for (center, radius) in [
+            ((0.0, 0.0), 500_000.0),
+            ((179.0, 60.0), 800_000.0),
+            ((-10.0, -84.0), 300_000.0),
+            ((100.0, 20.0), 5_000_000.0),
+        ] {
+            let mut expected: Vec<&str> = positions
+                .iter()
+                .filter(|(_, position)| haversine(center, *position) <= radius)
+                .map(|(member, _)| member.as_str())
+                .collect();
+            expected.sort();
+            let results = index.search(center, Shape::Radius(radius)).unwrap();
+            let mut found: Vec<&str> = results.iter().map(|(member, _)| member.as_str()).collect();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+}
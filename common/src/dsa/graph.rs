@@ -0,0 +1,428 @@
+//! This module provides a directed graph - structure of named nodes linked by weighted edges,
+//! with an optional label.
+//! Example:
+//! ```
+//! use common::dsa::graph::Graph;
+//!
+//! let mut graph = Graph::new("graph_name".to_string());
+//! for node in ["home", "office", "gym"] {
+//!     graph.add_node(node);
+//! }
+//! graph.add_edge("home", "office", 5.0, None).unwrap();
+//! graph.add_edge("home", "gym", 1.0, Some("walk".to_string())).unwrap();
+//! graph.add_edge("gym", "office", 2.0, None).unwrap();
+//!
+//! assert_eq!(graph.bfs("home"), Some(vec!["home", "gym", "office"]));
+//! assert_eq!(
+//!     graph.shortest_path("home", "office").unwrap(),
+//!     Some((3.0, vec!["home", "gym", "office"]))
+//! );
+//! ```
+//!
+//! Nodes keep their outgoing edges and the names of the nodes with an edge to them, so removing
+//! a node removes the edges in both directions. Neighbors are visited in name order.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, VecDeque};
+use std::fmt;
+
+use log::info;
+
+#[derive(Debug, PartialEq)]
+pub enum GraphError {
+    NodeNotFound(String),
+    /// Shortest paths need weights that are positive or zero.
+    InvalidWeight,
+    /// A deserialized graph is inconsistent.
+    InvalidSnapshot(&'static str),
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GraphError::NodeNotFound(node) => write!(f, "node {} not found", node),
+            GraphError::InvalidWeight => write!(f, "weight must be a positive number or zero"),
+            GraphError::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Edge {
+    pub weight: f64,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Node {
+    outgoing: BTreeMap<String, Edge>,
+    incoming: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "GraphRepr")
+)]
+pub struct Graph {
+    pub name: String,
+    nodes: BTreeMap<String, Node>,
+}
+
+/// Unchecked serialized form of a [`Graph`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct GraphRepr {
+    name: String,
+    nodes: BTreeMap<String, Node>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<GraphRepr> for Graph {
+    type Error = GraphError;
+
+    /// Checks that every edge links existing nodes and is recorded on both of them,
+    /// as traversals look up the nodes of the edges they follow.
+    fn try_from(repr: GraphRepr) -> Result<Self, GraphError> {
+        for (name, node) in &repr.nodes {
+            for (target, edge) in &node.outgoing {
+                if !(edge.weight >= 0.0 && edge.weight.is_finite()) {
+                    return Err(GraphError::InvalidWeight);
+                }
+                if !repr
+                    .nodes
+                    .get(target)
+                    .is_some_and(|target| target.incoming.contains(name))
+                {
+                    return Err(GraphError::InvalidSnapshot(
+                        "edge without its incoming entry",
+                    ));
+                }
+            }
+            for source in &node.incoming {
+                if !repr
+                    .nodes
+                    .get(source)
+                    .is_some_and(|source| source.outgoing.contains_key(name))
+                {
+                    return Err(GraphError::InvalidSnapshot(
+                        "incoming entry without its edge",
+                    ));
+                }
+            }
+        }
+        Ok(Graph {
+            name: repr.name,
+            nodes: repr.nodes,
+        })
+    }
+}
+
+/// Entry of the Dijkstra queue, ordered so the closest node is popped first.
+struct Candidate<'a> {
+    distance: f64,
+    node: &'a str,
+}
+
+impl PartialEq for Candidate<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate<'_> {}
+
+impl PartialOrd for Candidate<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate<'_> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .distance
+            .total_cmp(&self.distance)
+            .then_with(|| other.node.cmp(self.node))
+    }
+}
+
+impl Graph {
+    pub fn new(name: String) -> Self {
+        info!("Creating new graph with name: {}", name);
+        Graph {
+            name,
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    /// Adds the node. Returns false if it was already present.
+    pub fn add_node(&mut self, node: &str) -> bool {
+        if self.nodes.contains_key(node) {
+            return false;
+        }
+        info!("Adding node {} to graph {}", node, self.name);
+        self.nodes.insert(node.to_string(), Node::default());
+        true
+    }
+
+    /// Adds an edge between existing nodes, or replaces it. Returns false if it was replaced.
+    pub fn add_edge(
+        &mut self,
+        from: &str,
+        to: &str,
+        weight: f64,
+        label: Option<String>,
+    ) -> Result<bool, GraphError> {
+        if !(weight >= 0.0 && weight.is_finite()) {
+            return Err(GraphError::InvalidWeight);
+        }
+        for node in [from, to] {
+            if !self.nodes.contains_key(node) {
+                return Err(GraphError::NodeNotFound(node.to_string()));
+            }
+        }
+        info!("Adding edge {} -> {} to graph {}", from, to, self.name);
+        self.nodes
+            .get_mut(to)
+            .expect("checked above")
+            .incoming
+            .insert(from.to_string());
+        let edge = Edge { weight, label };
+        let previous = self
+            .nodes
+            .get_mut(from)
+            .expect("checked above")
+            .outgoing
+            .insert(to.to_string(), edge);
+        Ok(previous.is_none())
+    }
+
+    /// Removes the node with its edges in both directions. Returns false if it was not present.
+    pub fn remove_node(&mut self, node: &str) -> bool {
+        let removed = match self.nodes.remove(node) {
+            Some(removed) => removed,
+            None => return false,
+        };
+        info!("Removing node {} from graph {}", node, self.name);
+        for source in &removed.incoming {
+            if let Some(source) = self.nodes.get_mut(source) {
+                source.outgoing.remove(node);
+            }
+        }
+        for target in removed.outgoing.keys() {
+            if let Some(target) = self.nodes.get_mut(target) {
+                target.incoming.remove(node);
+            }
+        }
+        true
+    }
+
+    pub fn contains(&self, node: &str) -> bool {
+        self.nodes.contains_key(node)
+    }
+
+    /// Returns the outgoing edges of the node, by target name.
+    pub fn neighbors(&self, node: &str) -> Option<Vec<(&str, &Edge)>> {
+        self.nodes.get(node).map(|node| {
+            node.outgoing
+                .iter()
+                .map(|(target, edge)| (target.as_str(), edge))
+                .collect()
+        })
+    }
+
+    /// Returns the nodes reachable from start, in breadth-first order.
+    pub fn bfs(&self, start: &str) -> Option<Vec<&str>> {
+        let (start, _) = self.nodes.get_key_value(start)?;
+        let mut visited: BTreeSet<&str> = BTreeSet::from([start.as_str()]);
+        let mut queue = VecDeque::from([start.as_str()]);
+        let mut order = Vec::new();
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            for target in self.nodes[node].outgoing.keys() {
+                if visited.insert(target) {
+                    queue.push_back(target);
+                }
+            }
+        }
+        Some(order)
+    }
+
+    /// Returns the length and the nodes of a shortest path, or None if to is not reachable.
+    pub fn shortest_path(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> Result<Option<(f64, Vec<&str>)>, GraphError> {
+        let (from, _) = self
+            .nodes
+            .get_key_value(from)
+            .ok_or_else(|| GraphError::NodeNotFound(from.to_string()))?;
+        if !self.nodes.contains_key(to) {
+            return Err(GraphError::NodeNotFound(to.to_string()));
+        }
+
+        // Best known distance and previous node of every reached node.
+        let mut reached: HashMap<&str, (f64, Option<&str>)> =
+            HashMap::from([(from.as_str(), (0.0, None))]);
+        let mut queue = BinaryHeap::from([Candidate {
+            distance: 0.0,
+            node: from,
+        }]);
+        while let Some(Candidate { distance, node }) = queue.pop() {
+            if node == to {
+                let mut path = vec![node];
+                while let Some((_, Some(previous))) = reached.get(path[path.len() - 1]) {
+                    path.push(previous);
+                }
+                path.reverse();
+                return Ok(Some((distance, path)));
+            }
+            if distance > reached[node].0 {
+                // A shorter path to the node was queued after this one.
+                continue;
+            }
+            for (target, edge) in &self.nodes[node].outgoing {
+                let candidate = distance + edge.weight;
+                if reached
+                    .get(target.as_str())
+                    .is_none_or(|(best, _)| candidate < *best)
+                {
+                    reached.insert(target, (candidate, Some(node)));
+                    queue.push(Candidate {
+                        distance: candidate,
+                        node: target,
+                    });
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Number of nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_graph() -> Graph {
+        let mut graph = Graph::new("test".to_string());
+        for node in ["a", "b", "c", "d", "e"] {
+            graph.add_node(node);
+        }
+        for (from, to, weight) in [
+            ("a", "b", 4.0),
+            ("a", "c", 1.0),
+            ("c", "b", 2.0),
+            ("b", "d", 1.0),
+            ("c", "d", 5.0),
+            ("d", "a", 1.0),
+        ] {
+            graph.add_edge(from, to, weight, None).unwrap();
+        }
+        graph
+    }
+
+    #[test]
+    fn test_edges() {
+        let mut graph = setup_graph();
+        assert!(!graph.add_node("a"));
+        assert_eq!(
+            graph.add_edge("a", "x", 1.0, None),
+            Err(GraphError::NodeNotFound("x".to_string()))
+        );
+        assert_eq!(
+            graph.add_edge("a", "b", -1.0, None),
+            Err(GraphError::InvalidWeight)
+        );
+        assert_eq!(
+            graph.add_edge("a", "b", 3.0, Some("road".to_string())),
+            Ok(false)
+        );
+        let neighbors = graph.neighbors("a").unwrap();
+        assert_eq!(neighbors.len(), 2);
+        assert_eq!(
+            neighbors[0],
+            (
+                "b",
+                &Edge {
+                    weight: 3.0,
+                    label: Some("road".to_string())
+                }
+            )
+        );
+        assert_eq!(graph.neighbors("x"), None);
+    }
+
+    #[test]
+    fn test_bfs() {
+        let graph = setup_graph();
+        assert_eq!(graph.bfs("a"), Some(vec!["a", "b", "c", "d"]));
+        assert_eq!(graph.bfs("e"), Some(vec!["e"]));
+        assert_eq!(graph.bfs("x"), None);
+    }
+
+    #[test]
+    fn test_shortest_path() {
+        let graph = setup_graph();
+        assert_eq!(
+            graph.shortest_path("a", "d").unwrap(),
+            Some((4.0, vec!["a", "c", "b", "d"]))
+        );
+        assert_eq!(
+            graph.shortest_path("a", "a").unwrap(),
+            Some((0.0, vec!["a"]))
+        );
+        assert_eq!(graph.shortest_path("a", "e").unwrap(), None);
+        assert_eq!(
+            graph.shortest_path("x", "a"),
+            Err(GraphError::NodeNotFound("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_remove_node() {
+        let mut graph = setup_graph();
+        assert!(graph.remove_node("b"));
+        assert!(!graph.remove_node("b"));
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.neighbors("a").unwrap().len(), 1);
+        assert!(!graph.nodes["d"].incoming.contains("b"));
+        assert_eq!(
+            graph.shortest_path("a", "d").unwrap(),
+            Some((6.0, vec!["a", "c", "d"]))
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_invalid_snapshot() {
+        let graph = setup_graph();
+        let json = serde_json::to_value(&graph).unwrap();
+        let restored: Graph = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.bfs("a"), graph.bfs("a"));
+
+        let corrupt = |path: &str, value: serde_json::Value| {
+            let mut json = json.clone();
+            *json.pointer_mut(path).unwrap() = value;
+            serde_json::from_value::<Graph>(json)
+        };
+        let edge = serde_json::json!({"x": {"weight": 1.0, "label": null}});
+        assert!(corrupt("/nodes/a/outgoing", edge).is_err());
+        assert!(corrupt("/nodes/a/incoming", serde_json::json!(["e"])).is_err());
+        assert!(corrupt("/nodes/d/incoming", serde_json::json!([])).is_err());
+        assert!(corrupt("/nodes/a/outgoing/b/weight", (-1.0).into()).is_err());
+    }
+}
@@ -0,0 +1,17 @@
+//! Hash functions shared by the probabilistic structures. They are stable across
+//! Rust versions and platforms, so serialized structures stay valid.
+
+/// FNV-1a hash of the item.
+pub(crate) fn fnv1a(item: &str) -> u64 {
+    item.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Mixes the bits of x, so that every input bit affects every output bit.
+pub(crate) fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
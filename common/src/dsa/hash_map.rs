@@ -0,0 +1,109 @@
+//! This module provides a Hash Map - structure for keys that are looked up only as a whole,
+//! such as random IDs, where the prefix features of a char tree would be wasted.
+//! Example:
+//! ```
+//! use common::dsa::hash_map::HashMap;
+//!
+//! let mut map = HashMap::new("map_name".to_string());
+//! map.set("id", "somevalue");
+//!
+//! assert_eq!(map.get("id").unwrap(), "somevalue");
+//! assert!(map.del("id"));
+//! assert!(!map.exists("id"));
+//! ```
+
+use std::collections;
+
+use log::info;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HashMap {
+    pub name: String,
+    entries: collections::HashMap<String, String>,
+}
+
+impl HashMap {
+    pub fn new(name: String) -> Self {
+        info!("Creating new hash map with name: {}", name);
+        HashMap {
+            name,
+            entries: collections::HashMap::new(),
+        }
+    }
+
+    /// Sets the value of the key and returns the previous one.
+    pub fn set(&mut self, key: &str, value: &str) -> Option<String> {
+        info!("Setting value: {} to key: {}", value, key);
+        self.entries.insert(key.to_string(), value.to_string())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.entries.get(key)
+    }
+
+    /// Removes the key. Returns false if it was not present.
+    pub fn del(&mut self, key: &str) -> bool {
+        info!("Deleting key: {}", key);
+        self.entries.remove(key).is_some()
+    }
+
+    pub fn exists(&self, key: &str) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns all keys in ascending order.
+    pub fn keys(&self) -> Vec<&String> {
+        let mut keys: Vec<&String> = self.entries.keys().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Returns all entries in ascending key order.
+    pub fn scan(&self) -> Vec<(&String, &String)> {
+        info!("Scanning hash map {}", &self.name);
+        let mut entries: Vec<(&String, &String)> = self.entries.iter().collect();
+        entries.sort();
+        entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_get_del() {
+        let mut map = HashMap::new("test".to_string());
+        assert_eq!(map.set("b", "B"), None);
+        assert_eq!(map.set("a", "A"), None);
+        assert_eq!(map.set("b", "BB"), Some("B".to_string()));
+        assert_eq!(map.get("b").unwrap(), "BB");
+        assert_eq!(map.len(), 2);
+        assert!(map.exists("a"));
+
+        assert_eq!(map.keys(), vec!["a", "b"]);
+        assert_eq!(
+            map.scan(),
+            vec![
+                (&"a".to_string(), &"A".to_string()),
+                (&"b".to_string(), &"BB".to_string())
+            ]
+        );
+
+        assert!(map.del("a"));
+        assert!(!map.del("a"));
+        assert!(!map.exists("a"));
+        assert_eq!(map.get("a"), None);
+        assert_eq!(map.len(), 1);
+        assert!(!map.is_empty());
+    }
+}
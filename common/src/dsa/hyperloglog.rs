@@ -0,0 +1,284 @@
+//! This module provides a HyperLogLog - structure that estimates the number of distinct items
+//! added to it, with a standard error of 0.81% in at most 16 KiB.
+//! Example:
+//! ```
+//! use common::dsa::hyperloglog::HyperLogLog;
+//!
+//! let mut hll = HyperLogLog::new("hll_name".to_string());
+//! for visitor in ["alice", "bob", "alice"] {
+//!     hll.add(visitor);
+//! }
+//!
+//! assert_eq!(hll.count(), 2);
+//! ```
+//!
+//! Each item is hashed to one of 2^14 registers, which keeps the longest run of leading zeros
+//! seen in the rest of the hash. While few registers are set they are kept in a sorted sparse
+//! list, which is converted to a dense array of all registers once it would outgrow it.
+
+use std::fmt;
+
+use log::info;
+
+use super::hash::{fnv1a, splitmix64};
+
+const PRECISION: u32 = 14;
+const REGISTERS: usize = 1 << PRECISION;
+/// Largest sparse list before switching to the dense array, at which both take 16 KiB.
+const SPARSE_MAX: usize = REGISTERS / 4;
+
+#[derive(Debug, PartialEq)]
+pub enum HyperLogLogError {
+    /// A deserialized hyperloglog is inconsistent.
+    InvalidSnapshot(&'static str),
+}
+
+impl fmt::Display for HyperLogLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HyperLogLogError::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RegistersRepr")
+)]
+enum Registers {
+    /// Set registers only, as (index, value) sorted by index.
+    Sparse(Vec<(u16, u8)>),
+    Dense(Vec<u8>),
+}
+
+/// Unchecked serialized form of [`Registers`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+enum RegistersRepr {
+    Sparse(Vec<(u16, u8)>),
+    Dense(Vec<u8>),
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RegistersRepr> for Registers {
+    type Error = HyperLogLogError;
+
+    fn try_from(repr: RegistersRepr) -> Result<Self, HyperLogLogError> {
+        match repr {
+            RegistersRepr::Sparse(sparse) => {
+                if sparse
+                    .iter()
+                    .any(|(index, _)| usize::from(*index) >= REGISTERS)
+                {
+                    return Err(HyperLogLogError::InvalidSnapshot(
+                        "register index out of range",
+                    ));
+                }
+                if sparse.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    return Err(HyperLogLogError::InvalidSnapshot(
+                        "sparse registers not sorted by index",
+                    ));
+                }
+                Ok(Registers::Sparse(sparse))
+            }
+            RegistersRepr::Dense(dense) => {
+                if dense.len() != REGISTERS {
+                    return Err(HyperLogLogError::InvalidSnapshot(
+                        "wrong number of registers",
+                    ));
+                }
+                Ok(Registers::Dense(dense))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HyperLogLog {
+    pub name: String,
+    registers: Registers,
+}
+
+/// Returns the register of the item and the value it proposes for it.
+fn register_of(item: &str) -> (usize, u8) {
+    let hash = splitmix64(fnv1a(item));
+    let index = (hash >> (64 - PRECISION)) as usize;
+    // The guard bit bounds the run of zeros when the rest of the hash is zero.
+    let rest = (hash << PRECISION) | (1 << (PRECISION - 1));
+    (index, rest.leading_zeros() as u8 + 1)
+}
+
+impl HyperLogLog {
+    pub fn new(name: String) -> Self {
+        info!("Creating new hyperloglog with name: {}", name);
+        HyperLogLog {
+            name,
+            registers: Registers::Sparse(Vec::new()),
+        }
+    }
+
+    /// Raises the register to value if it is lower. Returns true if the register changed.
+    fn set_register(&mut self, index: usize, value: u8) -> bool {
+        match &mut self.registers {
+            Registers::Sparse(sparse) => {
+                match sparse.binary_search_by_key(&(index as u16), |(i, _)| *i) {
+                    Ok(position) if sparse[position].1 >= value => return false,
+                    Ok(position) => sparse[position].1 = value,
+                    Err(position) => sparse.insert(position, (index as u16, value)),
+                }
+                if sparse.len() > SPARSE_MAX {
+                    info!("Converting hyperloglog {} to dense", self.name);
+                    let mut dense = vec![0; REGISTERS];
+                    for (i, value) in sparse.iter() {
+                        dense[*i as usize] = *value;
+                    }
+                    self.registers = Registers::Dense(dense);
+                }
+                true
+            }
+            Registers::Dense(dense) => {
+                if dense[index] >= value {
+                    return false;
+                }
+                dense[index] = value;
+                true
+            }
+        }
+    }
+
+    /// Adds the item. Returns true if the estimate may have changed.
+    pub fn add(&mut self, item: &str) -> bool {
+        let (index, value) = register_of(item);
+        self.set_register(index, value)
+    }
+
+    /// Merges the other hyperloglog, so this one counts the items added to either.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        info!("Merging hyperloglog {} into {}", other.name, self.name);
+        match &other.registers {
+            Registers::Sparse(sparse) => {
+                for (index, value) in sparse {
+                    self.set_register(*index as usize, *value);
+                }
+            }
+            Registers::Dense(dense) => {
+                for (index, value) in dense.iter().enumerate().filter(|(_, v)| **v > 0) {
+                    self.set_register(index, *value);
+                }
+            }
+        }
+    }
+
+    /// Returns the estimated number of distinct items added.
+    pub fn count(&self) -> u64 {
+        let m = REGISTERS as f64;
+        // Sum of 2^-register over all registers, and the number of zero registers.
+        let (sum, zeros) = match &self.registers {
+            Registers::Sparse(sparse) => {
+                let zeros = REGISTERS - sparse.len();
+                let sum = sparse
+                    .iter()
+                    .map(|(_, value)| (-f64::from(*value)).exp2())
+                    .sum::<f64>();
+                (sum + zeros as f64, zeros)
+            }
+            Registers::Dense(dense) => (
+                dense
+                    .iter()
+                    .map(|value| (-f64::from(*value)).exp2())
+                    .sum::<f64>(),
+                dense.iter().filter(|value| **value == 0).count(),
+            ),
+        };
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let estimate = alpha * m * m / sum;
+        // Linear counting is more accurate for small cardinalities.
+        if estimate <= 2.5 * m && zeros > 0 {
+            return (m * (m / zeros as f64).ln()).round() as u64;
+        }
+        estimate.round() as u64
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.registers, Registers::Sparse(_))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn error(hll: &HyperLogLog, actual: u64) -> f64 {
+        (hll.count() as f64 - actual as f64).abs() / actual as f64
+    }
+
+    #[test]
+    fn test_add_and_count() {
+        let mut hll = HyperLogLog::new("test".to_string());
+        assert_eq!(hll.count(), 0);
+        assert!(hll.add("a"));
+        assert!(!hll.add("a"));
+        hll.add("b");
+        assert_eq!(hll.count(), 2);
+
+        for i in 0..1000 {
+            hll.add(&format!("visitor{}", i));
+        }
+        assert!(hll.is_sparse());
+        assert!(error(&hll, 1002) < 0.02);
+
+        for i in 1000..100_000 {
+            hll.add(&format!("visitor{}", i));
+        }
+        assert!(!hll.is_sparse());
+        assert!(error(&hll, 100_002) < 0.02, "{}", hll.count());
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut first = HyperLogLog::new("first".to_string());
+        let mut second = HyperLogLog::new("second".to_string());
+        for i in 0..20_000 {
+            first.add(&format!("visitor{}", i));
+        }
+        for i in 10_000..11_000 {
+            second.add(&format!("visitor{}", i));
+        }
+        for i in 20_000..21_000 {
+            second.add(&format!("visitor{}", i));
+        }
+
+        let mut merged = second.clone();
+        merged.merge(&first);
+        let mut other_way = first.clone();
+        other_way.merge(&second);
+        assert_eq!(merged.registers, other_way.registers);
+        assert!(error(&merged, 21_000) < 0.02, "{}", merged.count());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_invalid_snapshot() {
+        let parse = |registers: &str| {
+            serde_json::from_str::<HyperLogLog>(&format!(
+                r#"{{"name":"test","registers":{}}}"#,
+                registers
+            ))
+        };
+        assert!(parse(r#"{"Sparse":[[1,2],[5,1]]}"#).is_ok());
+        assert!(parse(r#"{"Sparse":[[16384,1]]}"#).is_err());
+        assert!(parse(r#"{"Sparse":[[5,1],[1,2]]}"#).is_err());
+        assert!(parse(r#"{"Dense":[0,0,0]}"#).is_err());
+
+        let mut hll = HyperLogLog::new("test".to_string());
+        for i in 0..10_000 {
+            hll.add(&format!("visitor{}", i));
+        }
+        let restored: HyperLogLog =
+            serde_json::from_str(&serde_json::to_string(&hll).unwrap()).unwrap();
+        assert_eq!(restored.registers, hll.registers);
+    }
+}
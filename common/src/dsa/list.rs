@@ -0,0 +1,125 @@
+//! This module provides a List - double-ended queue of values, for job queues.
+//! Example:
+//! ```
+//! use common::dsa::list::List;
+//!
+//! let mut list = List::new("list_name".to_string());
+//! list.push_back("first");
+//! list.push_back("second");
+//!
+//! assert_eq!(list.pop_front().unwrap(), "first");
+//! assert_eq!(list.len(), 1);
+//! ```
+
+use std::collections::VecDeque;
+
+use log::info;
+
+use super::range::resolve_range;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct List {
+    pub name: String,
+    items: VecDeque<String>,
+}
+
+impl List {
+    pub fn new(name: String) -> Self {
+        info!("Creating new list with name: {}", name);
+        List {
+            name,
+            items: VecDeque::new(),
+        }
+    }
+
+    /// Pushes the value to the front and returns the new length.
+    pub fn push_front(&mut self, value: &str) -> usize {
+        self.items.push_front(value.to_string());
+        self.items.len()
+    }
+
+    /// Pushes the value to the back and returns the new length.
+    pub fn push_back(&mut self, value: &str) -> usize {
+        self.items.push_back(value.to_string());
+        self.items.len()
+    }
+
+    pub fn pop_front(&mut self) -> Option<String> {
+        self.items.pop_front()
+    }
+
+    pub fn pop_back(&mut self) -> Option<String> {
+        self.items.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the values from start to stop, both inclusive.
+    /// Negative indexes count from the end, -1 being the last value.
+    pub fn range(&self, start: i64, stop: i64) -> Vec<&String> {
+        match resolve_range(self.items.len(), start, stop) {
+            Some((start, stop)) => self.items.range(start..=stop).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Keeps only the values from start to stop, both inclusive, like [`List::range`].
+    pub fn trim(&mut self, start: i64, stop: i64) {
+        info!("Trimming list {} to {}..={}", self.name, start, stop);
+        match resolve_range(self.items.len(), start, stop) {
+            Some((start, stop)) => {
+                self.items.truncate(stop + 1);
+                self.items.drain(..start);
+            }
+            None => self.items.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_list() -> List {
+        let mut list = List::new("test".to_string());
+        for value in ["b", "c", "d"] {
+            list.push_back(value);
+        }
+        list.push_front("a");
+        list
+    }
+
+    #[test]
+    fn test_push_and_pop() {
+        let mut list = setup_list();
+        assert_eq!(list.len(), 4);
+        assert_eq!(list.pop_front().unwrap(), "a");
+        assert_eq!(list.pop_back().unwrap(), "d");
+        assert_eq!(list.push_front("x"), 3);
+        assert_eq!(list.range(0, -1), vec!["x", "b", "c"]);
+
+        let mut empty = List::new("empty".to_string());
+        assert_eq!(empty.pop_front(), None);
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn test_range_and_trim() {
+        let mut list = setup_list();
+        assert_eq!(list.range(1, 2), vec!["b", "c"]);
+        assert_eq!(list.range(-2, 100), vec!["c", "d"]);
+        assert_eq!(list.range(3, 1), Vec::<&String>::new());
+
+        list.trim(1, -2);
+        assert_eq!(list.range(0, -1), vec!["b", "c"]);
+        list.trim(5, 10);
+        assert!(list.is_empty());
+    }
+}
@@ -1 +1,13 @@
+pub mod bloom;
 pub mod char_tree;
+pub mod full_text;
+pub mod geo;
+pub mod graph;
+mod hash;
+pub mod hash_map;
+pub mod hyperloglog;
+pub mod list;
+mod range;
+pub mod set;
+pub mod sorted_set;
+pub mod time_series;
@@ -0,0 +1,14 @@
+//! Range arithmetic shared by the structures with indexed reads.
+
+/// Resolves an inclusive range of indexes into a sequence of len items, negative indexes
+/// counting from the end, -1 being the last item. Returns None for an empty range.
+pub(crate) fn resolve_range(len: usize, start: i64, stop: i64) -> Option<(usize, usize)> {
+    let len = len as i64;
+    let resolve = |index: i64| if index < 0 { len + index } else { index };
+    let start = resolve(start).max(0);
+    let stop = resolve(stop).min(len - 1);
+    if start > stop {
+        return None;
+    }
+    Some((start as usize, stop as usize))
+}
@@ -0,0 +1,148 @@
+//! This module provides a Set - structure of unique members, with set algebra between sets.
+//! Example:
+//! ```
+//! use common::dsa::set::{self, Set};
+//!
+//! let mut admins = Set::new("admins".to_string());
+//! admins.add("alice");
+//! let mut users = Set::new("users".to_string());
+//! users.add("alice");
+//! users.add("bob");
+//!
+//! let regular = set::difference("regular".to_string(), &[&users, &admins]);
+//! assert_eq!(regular.members(), vec!["bob"]);
+//! ```
+
+use std::collections::HashSet;
+
+use log::info;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Set {
+    pub name: String,
+    members: HashSet<String>,
+}
+
+impl Set {
+    pub fn new(name: String) -> Self {
+        info!("Creating new set with name: {}", name);
+        Set {
+            name,
+            members: HashSet::new(),
+        }
+    }
+
+    /// Adds the member. Returns false if it was already present.
+    pub fn add(&mut self, member: &str) -> bool {
+        info!("Adding member: {}", member);
+        self.members.insert(member.to_string())
+    }
+
+    /// Removes the member. Returns false if it was not present.
+    pub fn remove(&mut self, member: &str) -> bool {
+        info!("Removing member: {}", member);
+        self.members.remove(member)
+    }
+
+    pub fn contains(&self, member: &str) -> bool {
+        self.members.contains(member)
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Returns all members in ascending order.
+    pub fn members(&self) -> Vec<&String> {
+        let mut members: Vec<&String> = self.members.iter().collect();
+        members.sort();
+        members
+    }
+}
+
+/// Returns a set named name with the members of any of the sets.
+pub fn union(name: String, sets: &[&Set]) -> Set {
+    let members = sets
+        .iter()
+        .flat_map(|set| set.members.iter().cloned())
+        .collect();
+    Set { name, members }
+}
+
+/// Returns a set named name with the members of all of the sets.
+pub fn intersection(name: String, sets: &[&Set]) -> Set {
+    let Some(smallest) = sets.iter().min_by_key(|set| set.len()) else {
+        return Set::new(name);
+    };
+    let members = smallest
+        .members
+        .iter()
+        .filter(|member| sets.iter().all(|set| set.contains(member)))
+        .cloned()
+        .collect();
+    Set { name, members }
+}
+
+/// Returns a set named name with the members of the first set that are in none of the others.
+pub fn difference(name: String, sets: &[&Set]) -> Set {
+    let Some((first, others)) = sets.split_first() else {
+        return Set::new(name);
+    };
+    let members = first
+        .members
+        .iter()
+        .filter(|member| !others.iter().any(|set| set.contains(member)))
+        .cloned()
+        .collect();
+    Set { name, members }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup_set(name: &str, members: &[&str]) -> Set {
+        let mut set = Set::new(name.to_string());
+        for member in members {
+            set.add(member);
+        }
+        set
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut set = setup_set("test", &["b", "a"]);
+        assert!(!set.add("a"));
+        assert!(set.contains("a"));
+        assert_eq!(set.members(), vec!["a", "b"]);
+        assert!(set.remove("a"));
+        assert!(!set.remove("a"));
+        assert_eq!(set.len(), 1);
+        assert!(!set.is_empty());
+    }
+
+    #[test]
+    fn test_algebra() {
+        let first = setup_set("first", &["a", "b", "c"]);
+        let second = setup_set("second", &["b", "c", "d"]);
+        let third = setup_set("third", &["c", "e"]);
+        let sets = [&first, &second, &third];
+
+        assert_eq!(
+            union("u".to_string(), &sets).members(),
+            vec!["a", "b", "c", "d", "e"]
+        );
+        assert_eq!(intersection("i".to_string(), &sets).members(), vec!["c"]);
+        assert_eq!(difference("d".to_string(), &sets).members(), vec!["a"]);
+        assert_eq!(
+            difference("d".to_string(), &[&second, &first]).members(),
+            vec!["d"]
+        );
+        assert!(intersection("i".to_string(), &[]).is_empty());
+    }
+}
@@ -0,0 +1,567 @@
+//! This module provides a Sorted Set - structure of unique members ordered by score,
+//! for leaderboards and priority queues.
+//! Example:
+//! ```
+//! use common::dsa::sorted_set::SortedSet;
+//!
+//! let mut set = SortedSet::new("set_name".to_string());
+//! set.add("alice", 30.0).unwrap();
+//! set.add("bob", 10.0).unwrap();
+//!
+//! assert_eq!(set.rank("alice"), Some(1));
+//! assert_eq!(set.range(0, 0), vec![("bob".to_string(), 10.0)]);
+//! ```
+//!
+//! Members are kept in an indexable skip list ordered by score, then by member, next to
+//! a map from member to score. Every link of the skip list knows how many members it skips,
+//! so ranks are computed on the way down, in O(log n) like lookups.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use log::info;
+
+use super::range::resolve_range;
+
+const MAX_LEVEL: usize = 32;
+const HEAD: usize = 0;
+
+#[derive(Debug, PartialEq)]
+pub enum ZsetError {
+    NotANumber,
+}
+
+impl fmt::Display for ZsetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZsetError::NotANumber => write!(f, "score is not a number"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Link {
+    next: Option<usize>,
+    /// Number of members between the node and next, counting next.
+    span: usize,
+}
+
+#[derive(Debug, Clone)]
+struct SkipNode {
+    member: String,
+    score: f64,
+    backward: Option<usize>,
+    levels: Vec<Link>,
+}
+
+impl SkipNode {
+    fn cmp_to(&self, score: f64, member: &str) -> Ordering {
+        self.score
+            .total_cmp(&score)
+            .then_with(|| self.member.as_str().cmp(member))
+    }
+}
+
+/// Skip list stored in an arena. Index 0 is the head, which holds no member.
+#[derive(Debug, Clone)]
+struct SkipList {
+    nodes: Vec<SkipNode>,
+    free: Vec<usize>,
+    level: usize,
+    len: usize,
+    rng: u64,
+}
+
+impl SkipList {
+    fn new() -> Self {
+        SkipList {
+            nodes: vec![SkipNode {
+                member: String::new(),
+                score: 0.0,
+                backward: None,
+                levels: vec![
+                    Link {
+                        next: None,
+                        span: 0
+                    };
+                    MAX_LEVEL
+                ],
+            }],
+            free: Vec::new(),
+            level: 1,
+            len: 0,
+            rng: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Each level is kept with probability 1/4, like in Redis.
+    fn random_level(&mut self) -> usize {
+        let mut level = 1;
+        loop {
+            // xorshift64
+            self.rng ^= self.rng << 13;
+            self.rng ^= self.rng >> 7;
+            self.rng ^= self.rng << 17;
+            if level == MAX_LEVEL || self.rng & 3 != 0 {
+                return level;
+            }
+            level += 1;
+        }
+    }
+
+    fn next(&self, node: usize, level: usize) -> Option<usize> {
+        self.nodes[node].levels[level].next
+    }
+
+    /// Returns for every level the last node before (score, member), and its rank.
+    fn find_predecessors(
+        &self,
+        score: f64,
+        member: &str,
+    ) -> ([usize; MAX_LEVEL], [usize; MAX_LEVEL]) {
+        let mut update = [HEAD; MAX_LEVEL];
+        let mut rank = [0; MAX_LEVEL];
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            rank[i] = if i == self.level - 1 { 0 } else { rank[i + 1] };
+            while let Some(next) = self.next(x, i) {
+                if self.nodes[next].cmp_to(score, member) != Ordering::Less {
+                    break;
+                }
+                rank[i] += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            update[i] = x;
+        }
+        (update, rank)
+    }
+
+    fn insert(&mut self, score: f64, member: String) {
+        let (mut update, mut rank) = self.find_predecessors(score, &member);
+        let level = self.random_level();
+        if level > self.level {
+            for i in self.level..level {
+                rank[i] = 0;
+                update[i] = HEAD;
+                self.nodes[HEAD].levels[i].span = self.len;
+            }
+            self.level = level;
+        }
+        let node = SkipNode {
+            member,
+            score,
+            backward: (update[0] != HEAD).then_some(update[0]),
+            levels: vec![
+                Link {
+                    next: None,
+                    span: 0
+                };
+                level
+            ],
+        };
+        let new = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        };
+        for i in 0..level {
+            let previous = self.nodes[update[i]].levels[i];
+            self.nodes[new].levels[i] = Link {
+                next: previous.next,
+                span: previous.span - (rank[0] - rank[i]),
+            };
+            self.nodes[update[i]].levels[i] = Link {
+                next: Some(new),
+                span: rank[0] - rank[i] + 1,
+            };
+        }
+        for (i, &previous) in update.iter().enumerate().take(self.level).skip(level) {
+            self.nodes[previous].levels[i].span += 1;
+        }
+        if let Some(next) = self.next(new, 0) {
+            self.nodes[next].backward = Some(new);
+        }
+        self.len += 1;
+    }
+
+    fn remove(&mut self, score: f64, member: &str) -> bool {
+        let (update, _) = self.find_predecessors(score, member);
+        let Some(x) = self.next(update[0], 0) else {
+            return false;
+        };
+        if self.nodes[x].cmp_to(score, member) != Ordering::Equal {
+            return false;
+        }
+        for (i, &previous) in update.iter().enumerate().take(self.level) {
+            if self.next(previous, i) == Some(x) {
+                let removed = self.nodes[x].levels[i];
+                let link = &mut self.nodes[previous].levels[i];
+                link.span += removed.span;
+                link.span -= 1;
+                link.next = removed.next;
+            } else {
+                self.nodes[previous].levels[i].span -= 1;
+            }
+        }
+        if let Some(next) = self.next(x, 0) {
+            self.nodes[next].backward = self.nodes[x].backward;
+        }
+        while self.level > 1 && self.next(HEAD, self.level - 1).is_none() {
+            self.level -= 1;
+        }
+        self.nodes[x].member = String::new();
+        self.nodes[x].levels = Vec::new();
+        self.free.push(x);
+        self.len -= 1;
+        true
+    }
+
+    /// Returns the 0-based rank of (score, member).
+    fn rank(&self, score: f64, member: &str) -> Option<usize> {
+        let mut rank = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.next(x, i) {
+                if self.nodes[next].cmp_to(score, member) == Ordering::Greater {
+                    break;
+                }
+                rank += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if x != HEAD && self.nodes[x].cmp_to(score, member) == Ordering::Equal {
+                return Some(rank - 1);
+            }
+        }
+        None
+    }
+
+    /// Returns the node with the given 0-based rank.
+    fn by_rank(&self, rank: usize) -> Option<usize> {
+        let target = rank + 1;
+        let mut traversed = 0;
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.next(x, i) {
+                if traversed + self.nodes[x].levels[i].span > target {
+                    break;
+                }
+                traversed += self.nodes[x].levels[i].span;
+                x = next;
+            }
+            if traversed == target {
+                return Some(x);
+            }
+        }
+        None
+    }
+
+    /// Returns the first node with a score of at least min.
+    fn first_from_score(&self, min: f64) -> Option<usize> {
+        let mut x = HEAD;
+        for i in (0..self.level).rev() {
+            while let Some(next) = self.next(x, i) {
+                if self.nodes[next].score.total_cmp(&min) != Ordering::Less {
+                    break;
+                }
+                x = next;
+            }
+        }
+        self.next(x, 0)
+    }
+
+    fn entry(&self, node: usize) -> (String, f64) {
+        (self.nodes[node].member.clone(), self.nodes[node].score)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "SortedSetRepr", into = "SortedSetRepr")
+)]
+pub struct SortedSet {
+    pub name: String,
+    list: SkipList,
+    scores: HashMap<String, f64>,
+}
+
+/// Serialized form of a [`SortedSet`]: the members in score order.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SortedSetRepr {
+    name: String,
+    members: Vec<(String, f64)>,
+}
+
+#[cfg(feature = "serde")]
+impl From<SortedSet> for SortedSetRepr {
+    fn from(set: SortedSet) -> Self {
+        SortedSetRepr {
+            members: set.range(0, -1),
+            name: set.name,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<SortedSetRepr> for SortedSet {
+    fn from(repr: SortedSetRepr) -> Self {
+        let mut set = SortedSet::new(repr.name);
+        for (member, score) in repr.members {
+            // NaN can't be serialized by the formats we use, skip it rather than fail.
+            let _ = set.add(&member, score);
+        }
+        set
+    }
+}
+
+impl SortedSet {
+    pub fn new(name: String) -> Self {
+        info!("Creating new sorted set with name: {}", name);
+        SortedSet {
+            name,
+            list: SkipList::new(),
+            scores: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.list.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.len == 0
+    }
+
+    /// Adds the member or updates its score. Returns true if the member is new.
+    pub fn add(&mut self, member: &str, score: f64) -> Result<bool, ZsetError> {
+        if score.is_nan() {
+            return Err(ZsetError::NotANumber);
+        }
+        info!("Adding member: {} with score: {}", member, score);
+        let is_new = match self.scores.get(member) {
+            Some(&old) if old.total_cmp(&score) == Ordering::Equal => return Ok(false),
+            Some(&old) => {
+                self.list.remove(old, member);
+                false
+            }
+            None => true,
+        };
+        self.list.insert(score, member.to_string());
+        self.scores.insert(member.to_string(), score);
+        Ok(is_new)
+    }
+
+    /// Removes the member. Returns false if it was not present.
+    pub fn remove(&mut self, member: &str) -> bool {
+        info!("Removing member: {}", member);
+        match self.scores.remove(member) {
+            Some(score) => self.list.remove(score, member),
+            None => false,
+        }
+    }
+
+    pub fn score(&self, member: &str) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+
+    /// Returns the 0-based rank of the member, lowest score first.
+    pub fn rank(&self, member: &str) -> Option<usize> {
+        let score = self.score(member)?;
+        self.list.rank(score, member)
+    }
+
+    /// Adds delta to the score of the member, which starts at 0 if missing. Returns the new score.
+    pub fn incr_by(&mut self, member: &str, delta: f64) -> Result<f64, ZsetError> {
+        let score = self.score(member).unwrap_or(0.0) + delta;
+        self.add(member, score)?;
+        Ok(score)
+    }
+
+    /// Returns the members with ranks from start to stop, both inclusive, lowest score first.
+    /// Negative ranks count from the end, -1 being the highest score.
+    pub fn range(&self, start: i64, stop: i64) -> Vec<(String, f64)> {
+        let Some((start, stop)) = resolve_range(self.len(), start, stop) else {
+            return Vec::new();
+        };
+        let mut result = Vec::with_capacity(stop - start + 1);
+        let mut node = self.list.by_rank(start);
+        while let Some(x) = node.filter(|_| result.len() <= stop - start) {
+            result.push(self.list.entry(x));
+            node = self.list.next(x, 0);
+        }
+        result
+    }
+
+    /// Like [`SortedSet::range`], with ranks counted from the highest score.
+    pub fn rev_range(&self, start: i64, stop: i64) -> Vec<(String, f64)> {
+        let Some((start, stop)) = resolve_range(self.len(), start, stop) else {
+            return Vec::new();
+        };
+        let mut result = Vec::with_capacity(stop - start + 1);
+        let mut node = self.list.by_rank(self.len() - 1 - start);
+        while let Some(x) = node.filter(|_| result.len() <= stop - start) {
+            result.push(self.list.entry(x));
+            node = self.list.nodes[x].backward;
+        }
+        result
+    }
+
+    /// Returns the members with a score between min and max, both inclusive, lowest score first.
+    pub fn range_by_score(&self, min: f64, max: f64) -> Vec<(String, f64)> {
+        let mut result = Vec::new();
+        let mut node = self.list.first_from_score(min);
+        while let Some(x) = node {
+            if self.list.nodes[x].score.total_cmp(&max) == Ordering::Greater {
+                break;
+            }
+            result.push(self.list.entry(x));
+            node = self.list.next(x, 0);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    fn setup_set() -> SortedSet {
+        let mut set = SortedSet::new("test".to_string());
+        for (member, score) in [("c", 3.0), ("a", 1.0), ("b", 2.0), ("d", 3.0)] {
+            set.add(member, score).unwrap();
+        }
+        set
+    }
+
+    fn members(entries: Vec<(String, f64)>) -> Vec<String> {
+        entries.into_iter().map(|(member, _)| member).collect()
+    }
+
+    #[test]
+    fn test_add_and_score() {
+        let mut set = setup_set();
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.add("a", 5.0), Ok(false));
+        assert_eq!(set.add("e", 0.5), Ok(true));
+        assert_eq!(set.score("a"), Some(5.0));
+        assert_eq!(set.add("x", f64::NAN), Err(ZsetError::NotANumber));
+        assert_eq!(members(set.range(0, -1)), vec!["e", "b", "c", "d", "a"]);
+    }
+
+    #[test]
+    fn test_rank_and_ranges() {
+        let set = setup_set();
+        assert_eq!(set.rank("a"), Some(0));
+        assert_eq!(set.rank("d"), Some(3));
+        assert_eq!(set.rank("x"), None);
+        assert_eq!(members(set.range(1, 2)), vec!["b", "c"]);
+        assert_eq!(members(set.range(-2, -1)), vec!["c", "d"]);
+        assert_eq!(members(set.range(3, 1)), Vec::<String>::new());
+        assert_eq!(members(set.rev_range(0, 1)), vec!["d", "c"]);
+        assert_eq!(members(set.rev_range(-1, 10)), vec!["a"]);
+        assert_eq!(members(set.range_by_score(2.0, 3.0)), vec!["b", "c", "d"]);
+        assert_eq!(
+            members(set.range_by_score(f64::NEG_INFINITY, 1.5)),
+            vec!["a"]
+        );
+    }
+
+    #[test]
+    fn test_remove_and_incr_by() {
+        let mut set = setup_set();
+        assert!(set.remove("b"));
+        assert!(!set.remove("b"));
+        assert_eq!(set.rank("c"), Some(1));
+        assert_eq!(set.incr_by("a", 10.0), Ok(11.0));
+        assert_eq!(set.incr_by("new", -1.0), Ok(-1.0));
+        assert_eq!(members(set.range(0, -1)), vec!["new", "c", "d", "a"]);
+        assert_eq!(set.incr_by("a", f64::INFINITY), Ok(f64::INFINITY));
+        assert_eq!(
+            set.incr_by("a", f64::NEG_INFINITY),
+            Err(ZsetError::NotANumber)
+        );
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Add(String, i8),
+        Remove(String),
+        IncrBy(String, i8),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        let member = "[a-h]";
+        prop_oneof![
+            (member, any::<i8>()).prop_map(|(m, s)| Op::Add(m, s)),
+            member.prop_map(Op::Remove),
+            (member, any::<i8>()).prop_map(|(m, s)| Op::IncrBy(m, s)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn prop_matches_model(ops in prop::collection::vec(op(), 0..60), start in -10i64..10, stop in -10i64..10) {
+            let mut set = SortedSet::new("prop".to_string());
+            let mut model: BTreeMap<String, f64> = BTreeMap::new();
+            for op in ops {
+                match op {
+                    Op::Add(member, score) => {
+                        let is_new = !model.contains_key(&member);
+                        model.insert(member.clone(), score.into());
+                        prop_assert_eq!(set.add(&member, score.into()), Ok(is_new));
+                    }
+                    Op::Remove(member) => {
+                        prop_assert_eq!(set.remove(&member), model.remove(&member).is_some());
+                    }
+                    Op::IncrBy(member, delta) => {
+                        let score = model.entry(member.clone()).or_insert(0.0);
+                        *score += f64::from(delta);
+                        prop_assert_eq!(set.incr_by(&member, delta.into()), Ok(*score));
+                    }
+                }
+            }
+            let mut sorted: Vec<(String, f64)> = model.into_iter().collect();
+            sorted.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+            prop_assert_eq!(set.len(), sorted.len());
+            prop_assert_eq!(set.range(0, -1), sorted.clone());
+            let mut reversed = sorted.clone();
+            reversed.reverse();
+            prop_assert_eq!(set.rev_range(0, -1), reversed.clone());
+            for (rank, (member, _)) in sorted.iter().enumerate() {
+                prop_assert_eq!(set.rank(member), Some(rank));
+            }
+
+            let expected = match resolve_range(sorted.len(), start, stop) {
+                Some((from, to)) => sorted[from..=to].to_vec(),
+                None => Vec::new(),
+            };
+            prop_assert_eq!(set.range(start, stop), expected);
+            let expected = match resolve_range(reversed.len(), start, stop) {
+                Some((from, to)) => reversed[from..=to].to_vec(),
+                None => Vec::new(),
+            };
+            prop_assert_eq!(set.rev_range(start, stop), expected);
+
+            let (min, max) = (start as f64 * 10.0, stop as f64 * 10.0);
+            let expected: Vec<(String, f64)> = sorted
+                .iter()
+                .filter(|(_, score)| (min..=max).contains(score))
+                .cloned()
+                .collect();
+            prop_assert_eq!(set.range_by_score(min, max), expected);
+        }
+    }
+}
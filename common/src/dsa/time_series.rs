@@ -0,0 +1,667 @@
+//! This module provides a time series - structure that keeps (timestamp, value) samples in
+//! timestamp order, compressed in chunks.
+//! Example:
+//! ```
+//! use common::dsa::time_series::{Aggregation, TimeSeries};
+//!
+//! let mut series = TimeSeries::new("series_name".to_string(), None);
+//! series.add(1000, 1.0).unwrap();
+//! series.add(2000, 3.0).unwrap();
+//! series.add(61000, 5.0).unwrap();
+//!
+//! assert_eq!(series.range(0, 2000), vec![(1000, 1.0), (2000, 3.0)]);
+//! assert_eq!(
+//!     series.aggregate(0, u64::MAX, Aggregation::Avg, 60000),
+//!     vec![(0, 2.0), (60000, 5.0)]
+//! );
+//! ```
+//!
+//! Chunks are encoded like in Facebook's Gorilla: timestamps as the difference between
+//! consecutive deltas (delta-of-delta), which is zero for regular samples, and values as the XOR
+//! with the previous value, of which only the bits that changed are written.
+//! With a retention, samples older than the retention before the last sample are dropped.
+
+use std::fmt;
+
+use log::info;
+
+/// Samples per chunk. A full chunk is sealed and a new one started.
+const CHUNK_SAMPLES: usize = 256;
+
+/// Delta-of-delta classes after the zero one: prefix, prefix length and value length in bits.
+/// Larger values are written after the prefix 1111 in 64 bits.
+const DOD_CLASSES: [(u64, u32, u32); 3] = [(0b10, 2, 7), (0b110, 3, 9), (0b1110, 4, 12)];
+
+#[derive(Debug, PartialEq)]
+pub enum TimeSeriesError {
+    /// Samples must be added in increasing timestamp order.
+    OutOfOrder { last: u64 },
+    /// A deserialized time series is inconsistent.
+    InvalidSnapshot(&'static str),
+}
+
+impl fmt::Display for TimeSeriesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeSeriesError::OutOfOrder { last } => {
+                write!(f, "timestamp must be after the last sample at {}", last)
+            }
+            TimeSeriesError::InvalidSnapshot(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Aggregation {
+    Avg,
+    Min,
+    Max,
+    Sum,
+    Count,
+}
+
+/// Samples of one aggregation bucket.
+struct Bucket {
+    start: u64,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Bucket {
+    fn new(start: u64, value: f64) -> Self {
+        Bucket {
+            start,
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn push(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    fn value(&self, aggregation: Aggregation) -> f64 {
+        match aggregation {
+            Aggregation::Avg => self.sum / self.count as f64,
+            Aggregation::Min => self.min,
+            Aggregation::Max => self.max,
+            Aggregation::Sum => self.sum,
+            Aggregation::Count => self.count as f64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct BitBuffer {
+    words: Vec<u64>,
+    len: u64,
+}
+
+impl BitBuffer {
+    /// Appends the n lowest bits of value, most significant first.
+    fn push(&mut self, value: u64, n: u32) {
+        if n == 0 {
+            return;
+        }
+        let value = if n == 64 {
+            value
+        } else {
+            value & ((1 << n) - 1)
+        };
+        let offset = (self.len % 64) as u32;
+        if offset == 0 {
+            self.words.push(0);
+        }
+        let free = 64 - offset;
+        let last = self.words.len() - 1;
+        if n <= free {
+            self.words[last] |= value << (free - n);
+        } else {
+            self.words[last] |= value >> (n - free);
+            self.words.push(value << (64 - (n - free)));
+        }
+        self.len += u64::from(n);
+    }
+}
+
+struct BitReader<'a> {
+    words: &'a [u64],
+    /// Number of bits written, which are the only ones read.
+    len: u64,
+    position: u64,
+}
+
+impl BitReader<'_> {
+    /// Reads the next n bits as the lowest bits of the result.
+    /// Returns None past the written bits.
+    fn read(&mut self, n: u32) -> Option<u64> {
+        if n == 0 {
+            return Some(0);
+        }
+        if self.position + u64::from(n) > self.len {
+            return None;
+        }
+        let index = (self.position / 64) as usize;
+        let offset = (self.position % 64) as u32;
+        let available = 64 - offset;
+        self.position += u64::from(n);
+        if n <= available {
+            return Some((self.words[index] << offset) >> (64 - n));
+        }
+        let rest = n - available;
+        let high = (self.words[index] << offset) >> offset;
+        Some((high << rest) | (self.words[index + 1] >> (64 - rest)))
+    }
+
+    fn read_bit(&mut self) -> Option<bool> {
+        Some(self.read(1)? == 1)
+    }
+}
+
+fn write_dod(bits: &mut BitBuffer, dod: i64) {
+    if dod == 0 {
+        bits.push(0, 1);
+        return;
+    }
+    for (prefix, prefix_len, len) in DOD_CLASSES {
+        let bound = 1 << (len - 1);
+        if (-bound..bound).contains(&dod) {
+            bits.push(prefix, prefix_len);
+            bits.push(dod as u64, len);
+            return;
+        }
+    }
+    bits.push(0b1111, 4);
+    bits.push(dod as u64, 64);
+}
+
+fn read_dod(reader: &mut BitReader) -> Option<i64> {
+    if !reader.read_bit()? {
+        return Some(0);
+    }
+    for (_, _, len) in DOD_CLASSES {
+        if !reader.read_bit()? {
+            // Sign-extend the len bits value.
+            return Some(((reader.read(len)? << (64 - len)) as i64) >> (64 - len));
+        }
+    }
+    Some(reader.read(64)? as i64)
+}
+
+/// Writes the XOR of a value with the previous one. If its bits fit in the window of the
+/// previous XOR only those are written, otherwise the new window is written before them.
+fn write_xor(bits: &mut BitBuffer, xor: u64, window: &mut Option<(u32, u32)>) {
+    if xor == 0 {
+        bits.push(0, 1);
+        return;
+    }
+    bits.push(1, 1);
+    // The leading zeros are written in 5 bits.
+    let leading = xor.leading_zeros().min(31);
+    let trailing = xor.trailing_zeros();
+    match *window {
+        Some((window_leading, window_trailing))
+            if leading >= window_leading && trailing >= window_trailing =>
+        {
+            bits.push(0, 1);
+            bits.push(
+                xor >> window_trailing,
+                64 - window_leading - window_trailing,
+            );
+        }
+        _ => {
+            let meaningful = 64 - leading - trailing;
+            bits.push(1, 1);
+            bits.push(u64::from(leading), 5);
+            bits.push(u64::from(meaningful - 1), 6);
+            bits.push(xor >> trailing, meaningful);
+            *window = Some((leading, trailing));
+        }
+    }
+}
+
+/// Reads the XOR written by [`write_xor`]. Returns None if the bits don't hold a valid one.
+fn read_xor(reader: &mut BitReader, window: &mut Option<(u32, u32)>) -> Option<u64> {
+    if !reader.read_bit()? {
+        return Some(0);
+    }
+    if reader.read_bit()? {
+        let leading = reader.read(5)? as u32;
+        let meaningful = reader.read(6)? as u32 + 1;
+        *window = Some((leading, 64u32.checked_sub(leading + meaningful)?));
+    }
+    // A window is written with the first XOR.
+    let (leading, trailing) = (*window)?;
+    Some(reader.read(64 - leading - trailing)? << trailing)
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "ChunkRepr")
+)]
+struct Chunk {
+    bits: BitBuffer,
+    count: usize,
+    first: u64,
+    last: u64,
+    /// Encoder state, to append the next sample.
+    last_delta: u64,
+    last_value: u64,
+    window: Option<(u32, u32)>,
+}
+
+impl Chunk {
+    fn new(timestamp: u64, value: f64) -> Self {
+        let mut bits = BitBuffer::default();
+        bits.push(timestamp, 64);
+        bits.push(value.to_bits(), 64);
+        Chunk {
+            bits,
+            count: 1,
+            first: timestamp,
+            last: timestamp,
+            last_delta: 0,
+            last_value: value.to_bits(),
+            window: None,
+        }
+    }
+
+    fn push(&mut self, timestamp: u64, value: f64) {
+        let delta = timestamp.wrapping_sub(self.last);
+        write_dod(&mut self.bits, delta.wrapping_sub(self.last_delta) as i64);
+        write_xor(
+            &mut self.bits,
+            value.to_bits() ^ self.last_value,
+            &mut self.window,
+        );
+        self.count += 1;
+        self.last = timestamp;
+        self.last_delta = delta;
+        self.last_value = value.to_bits();
+    }
+
+    fn iter(&self) -> ChunkIter<'_> {
+        ChunkIter {
+            reader: BitReader {
+                words: &self.bits.words,
+                len: self.bits.len,
+                position: 0,
+            },
+            remaining: self.count,
+            timestamp: 0,
+            delta: 0,
+            value: 0,
+            window: None,
+        }
+    }
+}
+
+/// Decoder of a chunk, mirroring the state of its encoder.
+struct ChunkIter<'a> {
+    reader: BitReader<'a>,
+    remaining: usize,
+    timestamp: u64,
+    delta: u64,
+    value: u64,
+    window: Option<(u32, u32)>,
+}
+
+impl Iterator for ChunkIter<'_> {
+    type Item = (u64, f64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        // Chunks are checked when deserialized, so the bits always hold count samples.
+        if self.reader.position == 0 {
+            self.timestamp = self.reader.read(64)?;
+            self.value = self.reader.read(64)?;
+        } else {
+            let dod = read_dod(&mut self.reader)?;
+            self.delta = self.delta.wrapping_add(dod as u64);
+            self.timestamp = self.timestamp.wrapping_add(self.delta);
+            self.value ^= read_xor(&mut self.reader, &mut self.window)?;
+        }
+        self.remaining -= 1;
+        Some((self.timestamp, f64::from_bits(self.value)))
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "TimeSeriesRepr")
+)]
+pub struct TimeSeries {
+    pub name: String,
+    /// Milliseconds of samples kept before the last one, all of them if None.
+    retention: Option<u64>,
+    chunks: Vec<Chunk>,
+}
+
+/// Unchecked serialized form of a [`Chunk`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct ChunkRepr {
+    bits: BitBuffer,
+    count: usize,
+    first: u64,
+    last: u64,
+    last_delta: u64,
+    last_value: u64,
+    window: Option<(u32, u32)>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<ChunkRepr> for Chunk {
+    type Error = TimeSeriesError;
+
+    /// Decodes the chunk once, checking that its bits hold exactly count samples in increasing
+    /// timestamp order and that the encoder state matches the last of them.
+    fn try_from(repr: ChunkRepr) -> Result<Self, TimeSeriesError> {
+        let invalid = TimeSeriesError::InvalidSnapshot;
+        if repr.count == 0 || repr.count > CHUNK_SAMPLES {
+            return Err(invalid("sample count out of range"));
+        }
+        if repr.bits.len.div_ceil(64) != repr.bits.words.len() as u64 {
+            return Err(invalid("bits don't match the bit count"));
+        }
+        let chunk = Chunk {
+            bits: repr.bits,
+            count: repr.count,
+            first: repr.first,
+            last: repr.last,
+            last_delta: repr.last_delta,
+            last_value: repr.last_value,
+            window: repr.window,
+        };
+        let mut samples = chunk.iter();
+        let mut timestamps = samples.by_ref().map(|(timestamp, _)| timestamp);
+        let first = timestamps.next();
+        let mut last = first;
+        for timestamp in timestamps {
+            if last.is_some_and(|last| timestamp <= last) {
+                return Err(invalid("samples out of order"));
+            }
+            last = Some(timestamp);
+        }
+        if samples.remaining != 0 || samples.reader.position != chunk.bits.len {
+            return Err(invalid("bits don't hold the sample count"));
+        }
+        if first != Some(chunk.first) || last != Some(chunk.last) {
+            return Err(invalid("first or last timestamp doesn't match the samples"));
+        }
+        if (samples.delta, samples.value, samples.window)
+            != (chunk.last_delta, chunk.last_value, chunk.window)
+        {
+            return Err(invalid("encoder state doesn't match the samples"));
+        }
+        Ok(chunk)
+    }
+}
+
+/// Unchecked serialized form of a [`TimeSeries`].
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct TimeSeriesRepr {
+    name: String,
+    retention: Option<u64>,
+    chunks: Vec<Chunk>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<TimeSeriesRepr> for TimeSeries {
+    type Error = TimeSeriesError;
+
+    fn try_from(repr: TimeSeriesRepr) -> Result<Self, TimeSeriesError> {
+        if repr
+            .chunks
+            .windows(2)
+            .any(|pair| pair[0].last >= pair[1].first)
+        {
+            return Err(TimeSeriesError::InvalidSnapshot("chunks out of order"));
+        }
+        Ok(TimeSeries {
+            name: repr.name,
+            retention: repr.retention,
+            chunks: repr.chunks,
+        })
+    }
+}
+
+impl TimeSeries {
+    pub fn new(name: String, retention: Option<u64>) -> Self {
+        info!(
+            "Creating new time series with name: {}, retention: {:?}",
+            name, retention
+        );
+        TimeSeries {
+            name,
+            retention,
+            chunks: Vec::new(),
+        }
+    }
+
+    pub fn retention(&self) -> Option<u64> {
+        self.retention
+    }
+
+    /// Adds a sample after the last one, then drops the chunks past the retention.
+    pub fn add(&mut self, timestamp: u64, value: f64) -> Result<(), TimeSeriesError> {
+        match self.chunks.last_mut() {
+            Some(chunk) if timestamp <= chunk.last => {
+                return Err(TimeSeriesError::OutOfOrder { last: chunk.last })
+            }
+            Some(chunk) if chunk.count < CHUNK_SAMPLES => chunk.push(timestamp, value),
+            _ => self.chunks.push(Chunk::new(timestamp, value)),
+        }
+        self.trim();
+        Ok(())
+    }
+
+    /// Timestamp of the last sample, None if there is none.
+    pub fn last_timestamp(&self) -> Option<u64> {
+        self.chunks.last().map(|chunk| chunk.last)
+    }
+
+    /// Timestamp of the oldest sample within the retention.
+    fn cutoff(&self) -> u64 {
+        match (self.retention, self.chunks.last()) {
+            (Some(retention), Some(chunk)) => chunk.last.saturating_sub(retention),
+            _ => 0,
+        }
+    }
+
+    /// Drops the chunks of which every sample is past the retention. Samples past it in the
+    /// oldest remaining chunk are skipped when reading.
+    fn trim(&mut self) {
+        let cutoff = self.cutoff();
+        let expired = self
+            .chunks
+            .iter()
+            .take_while(|chunk| chunk.last < cutoff)
+            .count();
+        if expired > 0 {
+            info!(
+                "Dropping {} expired chunks of time series {}",
+                expired, self.name
+            );
+            self.chunks.drain(..expired);
+        }
+    }
+
+    fn samples(&self, from: u64, to: u64) -> impl Iterator<Item = (u64, f64)> + '_ {
+        let from = from.max(self.cutoff());
+        self.chunks
+            .iter()
+            .filter(move |chunk| chunk.last >= from && chunk.first <= to)
+            .flat_map(Chunk::iter)
+            .filter(move |(timestamp, _)| (from..=to).contains(timestamp))
+    }
+
+    /// Returns the samples with a timestamp between from and to, inclusive.
+    pub fn range(&self, from: u64, to: u64) -> Vec<(u64, f64)> {
+        self.samples(from, to).collect()
+    }
+
+    /// Aggregates the samples between from and to in buckets of bucket milliseconds, aligned
+    /// to multiples of it. Returns the start and the aggregated value of non-empty buckets.
+    pub fn aggregate(
+        &self,
+        from: u64,
+        to: u64,
+        aggregation: Aggregation,
+        bucket: u64,
+    ) -> Vec<(u64, f64)> {
+        let bucket = bucket.max(1);
+        let mut buckets: Vec<Bucket> = Vec::new();
+        for (timestamp, value) in self.samples(from, to) {
+            let start = timestamp - timestamp % bucket;
+            match buckets.last_mut() {
+                Some(last) if last.start == start => last.push(value),
+                _ => buckets.push(Bucket::new(start, value)),
+            }
+        }
+        buckets
+            .iter()
+            .map(|bucket| (bucket.start, bucket.value(aggregation)))
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples(0, u64::MAX).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Memory used by the compressed samples, in bytes.
+    pub fn size(&self) -> usize {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.bits.words.len() * 8)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding_roundtrip() {
+        let mut series = TimeSeries::new("test".to_string(), None);
+        let mut expected = Vec::new();
+        let mut timestamp = 1_700_000_000_000;
+        for i in 0..1000u64 {
+            // Irregular intervals and values, to go through every encoding.
+            timestamp += match i % 7 {
+                0 => 1000,
+                1 => 1001,
+                2 => 1200,
+                3 => 4000,
+                4 => 1_000_000,
+                _ => 1,
+            };
+            let value = match i % 5 {
+                0 => 0.0,
+                1 => -1.5,
+                2 => i as f64 * 1e10,
+                3 => f64::MAX,
+                _ => 42.0,
+            };
+            series.add(timestamp, value).unwrap();
+            expected.push((timestamp, value));
+        }
+        assert_eq!(series.chunks.len(), 4);
+        assert_eq!(series.range(0, u64::MAX), expected);
+        assert_eq!(series.len(), 1000);
+        assert_eq!(
+            series.add(timestamp, 1.0),
+            Err(TimeSeriesError::OutOfOrder { last: timestamp })
+        );
+    }
+
+    #[test]
+    fn test_compression() {
+        let mut series = TimeSeries::new("test".to_string(), None);
+        for i in 0..10_000u64 {
+            series.add(i * 1000, (i / 10 % 10) as f64).unwrap();
+        }
+        // Regular timestamps and repeated values take one bit each, against 16 bytes raw.
+        assert!(series.size() < 10_000, "{} bytes", series.size());
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut series = TimeSeries::new("test".to_string(), None);
+        for (timestamp, value) in [(100, 1.0), (900, 3.0), (1000, -2.0), (3500, 4.0)] {
+            series.add(timestamp, value).unwrap();
+        }
+        let aggregate = |aggregation| series.aggregate(0, 3000, aggregation, 1000);
+        assert_eq!(aggregate(Aggregation::Avg), vec![(0, 2.0), (1000, -2.0)]);
+        assert_eq!(aggregate(Aggregation::Min), vec![(0, 1.0), (1000, -2.0)]);
+        assert_eq!(aggregate(Aggregation::Max), vec![(0, 3.0), (1000, -2.0)]);
+        assert_eq!(aggregate(Aggregation::Sum), vec![(0, 4.0), (1000, -2.0)]);
+        assert_eq!(aggregate(Aggregation::Count), vec![(0, 2.0), (1000, 1.0)]);
+        assert_eq!(
+            series.aggregate(900, u64::MAX, Aggregation::Count, 0),
+            vec![(900, 1.0), (1000, 1.0), (3500, 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_retention() {
+        let mut series = TimeSeries::new("test".to_string(), Some(500));
+        for timestamp in 0..1000 {
+            series.add(timestamp, timestamp as f64).unwrap();
+        }
+        assert_eq!(series.len(), 501);
+        assert_eq!(series.range(0, 600).first(), Some(&(499, 499.0)));
+        // Only the chunk holding the cutoff is kept past it.
+        assert_eq!(series.chunks.len(), 3);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_invalid_snapshot() {
+        let mut series = TimeSeries::new("test".to_string(), None);
+        for i in 0..300u64 {
+            series.add(i * 1000 + i % 3, (i % 7) as f64 * 1.5).unwrap();
+        }
+        let json = serde_json::to_value(&series).unwrap();
+        let restored: TimeSeries = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.range(0, u64::MAX), series.range(0, u64::MAX));
+
+        let corrupt = |pointer: &str, value: serde_json::Value| {
+            let mut json = json.clone();
+            *json.pointer_mut(pointer).unwrap() = value;
+            serde_json::from_value::<TimeSeries>(json)
+        };
+        assert!(corrupt("/chunks/0/count", 0.into()).is_err());
+        assert!(corrupt("/chunks/0/count", 500.into()).is_err());
+        assert!(corrupt("/chunks/1/count", 1.into()).is_err());
+        assert!(corrupt("/chunks/0/bits/len", 10_000.into()).is_err());
+        assert!(corrupt("/chunks/0/bits/words", serde_json::json!([])).is_err());
+        assert!(corrupt("/chunks/0/bits/words/0", u64::MAX.into()).is_err());
+        assert!(corrupt("/chunks/0/first", 1.into()).is_err());
+        assert!(corrupt("/chunks/0/window", serde_json::json!([40, 40])).is_err());
+        assert!(corrupt("/chunks/1/first", 0.into()).is_err());
+        let mut swapped = json.clone();
+        swapped["chunks"].as_array_mut().unwrap().swap(0, 1);
+        assert!(serde_json::from_value::<TimeSeries>(swapped).is_err());
+    }
+}
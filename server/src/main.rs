@@ -5,7 +5,7 @@ use std::sync::Arc;
 
 use log::{debug, error, info, warn};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::TcpListener;
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
 
 use request_token::RequestToken;
@@ -43,7 +43,7 @@ async fn run_server(platform: Arc<RwLock<platform::Platform>>) -> tokio::io::Res
 }
 
 async fn handle_connection(
-    mut socket: tokio::net::TcpStream,
+    mut socket: TcpStream,
     platform: Arc<RwLock<platform::Platform>>,
 ) -> tokio::io::Result<()> {
     let mut buffer = [0; 1024];
@@ -71,7 +71,20 @@ async fn handle_connection(
             }
         };
         debug!("Parsed operation: {:?}", request_token);
-        match request_token.execute(&platform).await {
+        let result = if request_token.is_blocking() {
+            // Stop waiting when the client goes away, so a blocking pop doesn't take
+            // a value nobody will receive.
+            tokio::select! {
+                result = request_token.execute(&platform) => result,
+                _ = closed(&socket) => {
+                    info!("Connection closed by client while blocked.");
+                    return Ok(());
+                }
+            }
+        } else {
+            request_token.execute(&platform).await
+        };
+        match result {
             Ok(response) => {
                 if let Err(e) = socket.write_all(response.as_bytes()).await {
                     error!("Failed to write response: {}", e);
@@ -94,3 +107,63 @@ async fn handle_connection(
         }
     }
 }
+
+/// Resolves when the client closes the connection. Data sent in the meantime
+/// is left in the socket for the next request.
+async fn closed(socket: &TcpStream) {
+    let mut byte = [0; 1];
+    match socket.peek(&mut byte).await {
+        Ok(0) | Err(_) => {}
+        Ok(_) => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Sends the request and reads its reply, up to the blank line ending it.
+    async fn request(socket: &mut TcpStream, request: &str) -> String {
+        socket.write_all(request.as_bytes()).await.unwrap();
+        let mut reply = Vec::new();
+        let mut buffer = [0; 1024];
+        while !reply.ends_with(b"\n\n") {
+            let n = socket.read(&mut buffer).await.unwrap();
+            assert!(n > 0, "connection closed");
+            reply.extend_from_slice(&buffer[..n]);
+        }
+        String::from_utf8(reply).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_disconnected_blocking_pop_takes_no_value() {
+        let platform = Arc::new(RwLock::new(platform::Platform::new()));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        {
+            let platform = Arc::clone(&platform);
+            tokio::spawn(async move {
+                loop {
+                    let (socket, _) = listener.accept().await.unwrap();
+                    tokio::spawn(handle_connection(socket, Arc::clone(&platform)));
+                }
+            });
+        }
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        assert_eq!(
+            request(&mut client, "create list jobs").await,
+            "List jobs created\n\n"
+        );
+        let mut blocked = TcpStream::connect(address).await.unwrap();
+        blocked.write_all(b"list jobs blpop 0").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(blocked);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(request(&mut client, "list jobs rpush job1").await, "1\n\n");
+        assert_eq!(request(&mut client, "list jobs lpop").await, "job1\n\n");
+    }
+}
@@ -1,9 +1,19 @@
 use std::{collections::HashMap, sync::Arc};
 
 use log::debug;
-use tokio::sync::RwLock;
+use tokio::sync::{Notify, OwnedRwLockReadGuard, RwLock};
 
+use common::dsa::bloom::BloomFilter;
 use common::dsa::char_tree::CharTree;
+use common::dsa::full_text::FullTextIndex;
+use common::dsa::geo::GeoIndex;
+use common::dsa::graph::Graph;
+use common::dsa::hash_map;
+use common::dsa::hyperloglog::HyperLogLog;
+use common::dsa::list::List;
+use common::dsa::set::Set;
+use common::dsa::sorted_set::SortedSet;
+use common::dsa::time_series::TimeSeries;
 
 #[derive(Debug)]
 pub struct Platform {
@@ -18,48 +28,253 @@ impl Platform {
         }
     }
 
-    pub async fn r_lock_data_structures<'a>(&self) -> tokio::sync::RwLockReadGuard<DataStructures> {
+    pub async fn r_lock_data_structures(&self) -> tokio::sync::RwLockReadGuard<'_, DataStructures> {
         self.data_structures.read().await
     }
 
-    pub async fn rw_lock_data_structures(&self) -> tokio::sync::RwLockWriteGuard<DataStructures> {
+    pub async fn rw_lock_data_structures(
+        &self,
+    ) -> tokio::sync::RwLockWriteGuard<'_, DataStructures> {
         self.data_structures.write().await
     }
+
+    /// Runs f on the named structure, see [`DataStructures::with`].
+    pub async fn with<T: Structure, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, String> {
+        self.rw_lock_data_structures().await.with(name, f).await
+    }
+
+    /// Runs f on the named structure, see [`DataStructures::with_ref`].
+    pub async fn with_ref<T: Structure, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<R, String> {
+        self.r_lock_data_structures().await.with_ref(name, f).await
+    }
 }
 
+/// A data structure that can be kept in a [`Registry`], under its own name.
+pub trait Structure: Sized {
+    /// Type name used in replies, e.g. "Ctree not found".
+    const KIND: &'static str;
+
+    fn name(&self) -> &str;
+
+    /// The registry holding all structures of this type.
+    fn registry(data_structures: &DataStructures) -> &Registry<Self>;
+}
+
+/// All structures of one type, by name.
 #[derive(Debug)]
-pub struct DataStructures {
-    ctrees: RwLock<HashMap<String, Arc<RwLock<CharTree>>>>, // Wrap each CharTree in Arc and RwLock
+pub struct Registry<T> {
+    structures: RwLock<HashMap<String, Arc<RwLock<T>>>>, // Wrap each structure in Arc and RwLock
 }
 
-impl DataStructures {
+impl<T: Structure> Registry<T> {
     fn new() -> Self {
-        DataStructures {
-            ctrees: RwLock::new(HashMap::new()), // Initialize RwLock around the HashMap
+        Registry {
+            structures: RwLock::new(HashMap::new()), // Initialize RwLock around the HashMap
+        }
+    }
+
+    pub async fn insert(&self, structure: T) {
+        debug!("Inserting structure: {}", structure.name());
+        let mut structures = self.structures.write().await;
+        structures.insert(
+            structure.name().to_string(),
+            Arc::new(RwLock::new(structure)),
+        ); // Insert the structure wrapped in Arc and RwLock
+    }
+
+    pub async fn remove(&self, name: &str) {
+        debug!("Removing structure: {}", name);
+        let mut structures = self.structures.write().await; // Lock the HashMap for writing
+        structures.remove(name);
+    }
+
+    // Method to get a reference to a structure
+    pub async fn get(&self, name: &str) -> Option<Arc<RwLock<T>>> {
+        debug!("Getting structure: {}", name);
+        let structures = self.structures.read().await; // Lock the HashMap for reading
+        structures.get(name).cloned() // Clone the Arc to return a reference
+    }
+
+    /// Read-locks the named structures, each once, always in name order so that
+    /// two callers locking overlapping structures can't deadlock.
+    /// Returns None if any of them doesn't exist.
+    pub async fn read_many(
+        &self,
+        names: &[String],
+    ) -> Option<HashMap<String, OwnedRwLockReadGuard<T>>> {
+        let mut sorted: Vec<&String> = names.iter().collect();
+        sorted.sort();
+        sorted.dedup();
+        let mut guards = HashMap::new();
+        for name in sorted {
+            let structure = self.get(name).await?;
+            guards.insert(name.clone(), structure.read_owned().await);
+        }
+        Some(guards)
+    }
+
+    pub async fn get_all(&self) -> Vec<Arc<RwLock<T>>> {
+        let structures = self.structures.read().await;
+        structures.values().cloned().collect()
+    }
+}
+
+/// Declares [`DataStructures`] with one registry per structure type,
+/// and the [`Structure`] impl of each type.
+macro_rules! data_structures {
+    ($($field:ident: $type:ty => $kind:literal),* $(,)?) => {
+        /// One registry per structure type. Names are scoped to the type,
+        /// so a ctree and a hmap can share a name.
+        #[derive(Debug)]
+        pub struct DataStructures {
+            $($field: Registry<$type>,)*
+            /// Woken whenever a value is pushed to the list of the same name, for blocking pops.
+            list_pushes: RwLock<HashMap<String, Arc<Notify>>>,
+        }
+
+        impl DataStructures {
+            fn new() -> Self {
+                DataStructures {
+                    $($field: Registry::new(),)*
+                    list_pushes: RwLock::new(HashMap::new()),
+                }
+            }
         }
+
+        $(
+            impl Structure for $type {
+                const KIND: &'static str = $kind;
+
+                fn name(&self) -> &str {
+                    &self.name
+                }
+
+                fn registry(data_structures: &DataStructures) -> &Registry<Self> {
+                    &data_structures.$field
+                }
+            }
+        )*
+    };
+}
+
+data_structures! {
+    ctrees: CharTree => "Ctree",
+    hmaps: hash_map::HashMap => "Hmap",
+    zsets: SortedSet => "Zset",
+    lists: List => "List",
+    sets: Set => "Set",
+    blooms: BloomFilter => "Bloom",
+    hlls: HyperLogLog => "Hll",
+    series: TimeSeries => "Ts",
+    graphs: Graph => "Graph",
+    indexes: FullTextIndex => "Fts",
+    geos: GeoIndex => "Geo",
+}
+
+impl DataStructures {
+    /// Returns the named structure, or a "<Kind> not found" error.
+    pub async fn get<T: Structure>(&self, name: &str) -> Result<Arc<RwLock<T>>, String> {
+        T::registry(self)
+            .get(name)
+            .await
+            .ok_or_else(|| format!("{} not found", T::KIND))
+    }
+
+    /// Returns whether the named structure exists.
+    pub async fn exists<T: Structure>(&self, name: &str) -> bool {
+        T::registry(self).get(name).await.is_some()
+    }
+
+    /// Returns every structure of the type.
+    pub async fn get_all<T: Structure>(&self) -> Vec<Arc<RwLock<T>>> {
+        T::registry(self).get_all().await
+    }
+
+    /// Read-locks the named structures, see [`Registry::read_many`].
+    /// Fails with "<Kind> not found" if any of them doesn't exist.
+    pub async fn read_many<T: Structure>(
+        &self,
+        names: &[String],
+    ) -> Result<HashMap<String, OwnedRwLockReadGuard<T>>, String> {
+        T::registry(self)
+            .read_many(names)
+            .await
+            .ok_or_else(|| format!("{} not found", T::KIND))
+    }
+
+    /// Runs f on the named structure, holding its write lock.
+    pub async fn with<T: Structure, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&mut T) -> R,
+    ) -> Result<R, String> {
+        let structure = self.get::<T>(name).await?;
+        let mut structure = structure.write().await;
+        Ok(f(&mut structure))
+    }
+
+    /// Runs f on the named structure, holding its read lock.
+    pub async fn with_ref<T: Structure, R>(
+        &self,
+        name: &str,
+        f: impl FnOnce(&T) -> R,
+    ) -> Result<R, String> {
+        let structure = self.get::<T>(name).await?;
+        let structure = structure.read().await;
+        Ok(f(&structure))
+    }
+
+    /// Adds the structure, replacing any of the same type and name.
+    pub async fn create<T: Structure>(&self, structure: T) -> String {
+        let reply = format!("{} {} created", T::KIND, structure.name());
+        self.replace(structure).await;
+        reply
     }
 
-    pub async fn insert_ctree(&self, tree: CharTree) {
-        debug!("Inserting ctree: {}", tree.name);
-        let mut ctrees = self.ctrees.write().await;
-        ctrees.insert(tree.name.clone(), Arc::new(RwLock::new(tree))); // Insert the tree wrapped in Arc and RwLock
+    /// Adds the structure, replacing any of the same type and name, without a reply.
+    pub async fn replace<T: Structure>(&self, structure: T) {
+        T::registry(self).insert(structure).await;
     }
 
-    pub async fn remove_ctree(&self, name: &str) {
-        debug!("Removing ctree: {}", name);
-        let mut ctrees = self.ctrees.write().await; // Lock the HashMap for writing
-        ctrees.remove(name);
+    /// Removes the named structure, if it exists.
+    pub async fn destroy<T: Structure>(&self, name: &str) -> String {
+        T::registry(self).remove(name).await;
+        format!("{} {} removed", T::KIND, name)
     }
 
-    // Method to get a reference to a ctree
-    pub async fn get_ctree(&self, name: &str) -> Option<Arc<RwLock<CharTree>>> {
-        debug!("Getting ctree: {}", name);
-        let ctrees = self.ctrees.read().await; // Lock the HashMap for reading
-        ctrees.get(name).cloned() // Clone the Arc to return a reference
+    /// Returns the notifier of pushes to the named list, for a pop to wait on.
+    /// Only call it for an existing list, [`Self::remove_list`] drops it with the list.
+    pub async fn list_pushes(&self, name: &str) -> Arc<Notify> {
+        let mut list_pushes = self.list_pushes.write().await;
+        list_pushes.entry(name.to_string()).or_default().clone()
     }
 
-    pub async fn get_all_ctrees(&self) -> Vec<Arc<RwLock<CharTree>>> {
-        let ctrees = self.ctrees.read().await;
-        ctrees.values().cloned().collect()
+    /// Wakes up the pops waiting on the named list, if any.
+    pub async fn notify_list_pushes(&self, name: &str) {
+        if let Some(notify) = self.list_pushes.read().await.get(name) {
+            notify.notify_waiters();
+        }
+    }
+
+    #[cfg(test)]
+    pub async fn has_list_pushes(&self, name: &str) -> bool {
+        self.list_pushes.read().await.contains_key(name)
+    }
+
+    /// Removes the list and wakes up its blocked pops, so they see that it is gone.
+    pub async fn remove_list(&self, name: &str) -> String {
+        let reply = self.destroy::<List>(name).await;
+        if let Some(notify) = self.list_pushes.write().await.remove(name) {
+            notify.notify_waiters();
+        }
+        reply
     }
 }
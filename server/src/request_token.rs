@@ -1,10 +1,43 @@
+mod bloom;
+mod fts;
+mod geo;
+mod graph;
+mod hll;
+mod hmap;
+mod list;
+mod set;
+mod ts;
+mod zset;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::sync::Arc;
 
-use common::dsa::char_tree::CharTree;
-use log::{debug, info};
+use common::dsa::bloom::BloomFilter;
+use common::dsa::char_tree::{CharTree, MergePolicy, VersionPoint};
+use common::dsa::full_text::FullTextIndex;
+use common::dsa::geo::GeoIndex;
+use common::dsa::graph::Graph;
+use common::dsa::hash_map::HashMap;
+use common::dsa::hyperloglog::HyperLogLog;
+use common::dsa::list::List;
+use common::dsa::set::Set;
+use common::dsa::sorted_set::SortedSet;
+use common::dsa::time_series::TimeSeries;
+use log::debug;
 use tokio::sync::RwLock;
 
-use crate::platform::Platform;
+use crate::platform::{DataStructures, Platform};
+use bloom::BloomOpType;
+use fts::FtsOpType;
+use geo::GeoOpType;
+use graph::GraphOpType;
+use hll::HllOpType;
+use hmap::HmapOpType;
+use list::ListOpType;
+use set::SetOpType;
+use ts::TsOpType;
+use zset::ZsetOpType;
 
 fn split_once_or_err<'a>(
     input: &'a str,
@@ -15,6 +48,13 @@ fn split_once_or_err<'a>(
         .ok_or(RequestParserError::InvalidRequest)
 }
 
+/// Parses a number or other value from a request argument.
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T, RequestParserError> {
+    value
+        .parse()
+        .map_err(|_| RequestParserError::InvalidRequest)
+}
+
 #[derive(PartialEq, Debug)]
 pub enum RequestParserError {
     InvalidRequest,
@@ -27,9 +67,58 @@ impl RequestParserError {
     }
 }
 
+/// Versions kept per key when `create ctree <name> versioned` gives no limit.
+const DEFAULT_HISTORY_LIMIT: usize = 16;
+
+/// Parameters of `create bloom <name>` without capacity and error rate.
+const DEFAULT_BLOOM_CAPACITY: usize = 100;
+const DEFAULT_BLOOM_ERROR_RATE: f64 = 0.01;
+
+/// Largest `setrange` offset accepted, so a request can't make the server pad a value
+/// with an arbitrary amount of memory.
+const MAX_SETRANGE_OFFSET: usize = 1 << 20;
+
 #[derive(Debug)]
 pub enum DataStructureType {
-    Ctree { name: String },
+    Ctree {
+        name: String,
+        history_limit: Option<usize>,
+    },
+    Hmap {
+        name: String,
+    },
+    Zset {
+        name: String,
+    },
+    List {
+        name: String,
+    },
+    Set {
+        name: String,
+    },
+    Bloom {
+        name: String,
+        /// Capacity and error rate, if given.
+        parameters: Option<(usize, f64)>,
+    },
+    Hll {
+        name: String,
+    },
+    Ts {
+        name: String,
+        /// Milliseconds of samples kept before the last one, if given.
+        retention: Option<u64>,
+    },
+    Graph {
+        name: String,
+    },
+    Fts {
+        name: String,
+        stemming: bool,
+    },
+    Geo {
+        name: String,
+    },
 }
 
 impl TryFrom<String> for DataStructureType {
@@ -37,7 +126,80 @@ impl TryFrom<String> for DataStructureType {
     fn try_from(value: String) -> Result<Self, RequestParserError> {
         let (structure_type, structure_name) = split_once_or_err(&value, " ")?;
         match structure_type {
-            "ctree" => Ok(DataStructureType::Ctree {
+            "ctree" => {
+                // Names may contain spaces, so only a trailing `versioned [limit]` is read
+                // as an option and anything else is part of the name.
+                let (name, history_limit) = match structure_name.rsplit_once(' ') {
+                    Some((name, "versioned")) => (name, Some(DEFAULT_HISTORY_LIMIT)),
+                    Some((rest, limit)) => match (rest.rsplit_once(' '), limit.parse().ok()) {
+                        (Some((name, "versioned")), Some(limit)) => (name, Some(limit)),
+                        _ => (structure_name, None),
+                    },
+                    None => (structure_name, None),
+                };
+                Ok(DataStructureType::Ctree {
+                    name: name.to_string(),
+                    history_limit,
+                })
+            }
+            "hmap" => Ok(DataStructureType::Hmap {
+                name: structure_name.to_string(),
+            }),
+            "zset" => Ok(DataStructureType::Zset {
+                name: structure_name.to_string(),
+            }),
+            "list" => Ok(DataStructureType::List {
+                name: structure_name.to_string(),
+            }),
+            "set" => Ok(DataStructureType::Set {
+                name: structure_name.to_string(),
+            }),
+            "bloom" => {
+                let mut args = structure_name.split(' ');
+                let name = args.next().unwrap_or_default();
+                let parameters = match (args.next(), args.next(), args.next()) {
+                    (None, _, _) => None,
+                    (Some(capacity), Some(error_rate), None) => {
+                        Some((parse(capacity)?, parse(error_rate)?))
+                    }
+                    _ => return Err(RequestParserError::InvalidRequest),
+                };
+                Ok(DataStructureType::Bloom {
+                    name: name.to_string(),
+                    parameters,
+                })
+            }
+            "hll" => Ok(DataStructureType::Hll {
+                name: structure_name.to_string(),
+            }),
+            "ts" => {
+                let mut args = structure_name.split(' ');
+                let name = args.next().unwrap_or_default();
+                let retention = match (args.next(), args.next()) {
+                    (None, _) => None,
+                    (Some(retention), None) => Some(parse(retention)?),
+                    _ => return Err(RequestParserError::InvalidRequest),
+                };
+                Ok(DataStructureType::Ts {
+                    name: name.to_string(),
+                    retention,
+                })
+            }
+            "graph" => Ok(DataStructureType::Graph {
+                name: structure_name.to_string(),
+            }),
+            "fts" => {
+                let (name, stemming) = match structure_name.split_once(' ') {
+                    Some((name, "stem")) => (name, true),
+                    Some(_) => return Err(RequestParserError::InvalidRequest),
+                    None => (structure_name, false),
+                };
+                Ok(DataStructureType::Fts {
+                    name: name.to_string(),
+                    stemming,
+                })
+            }
+            "geo" => Ok(DataStructureType::Geo {
                 name: structure_name.to_string(),
             }),
             _ => Err(RequestParserError::InvalidRequest),
@@ -45,18 +207,106 @@ impl TryFrom<String> for DataStructureType {
     }
 }
 
+impl DataStructureType {
+    /// Creates the empty structure, replacing any of the same type and name.
+    async fn create(&self, data_structures: &DataStructures) -> Result<String, String> {
+        let reply = match self {
+            DataStructureType::Ctree {
+                name,
+                history_limit,
+            } => {
+                let ctree = match history_limit {
+                    Some(limit) => CharTree::new_versioned(name.clone(), *limit),
+                    None => CharTree::new(name.clone()),
+                };
+                data_structures.create(ctree).await
+            }
+            DataStructureType::Hmap { name } => {
+                data_structures.create(HashMap::new(name.clone())).await
+            }
+            DataStructureType::Zset { name } => {
+                data_structures.create(SortedSet::new(name.clone())).await
+            }
+            DataStructureType::List { name } => {
+                data_structures.create(List::new(name.clone())).await
+            }
+            DataStructureType::Set { name } => data_structures.create(Set::new(name.clone())).await,
+            DataStructureType::Bloom { name, parameters } => {
+                let (capacity, error_rate) =
+                    parameters.unwrap_or((DEFAULT_BLOOM_CAPACITY, DEFAULT_BLOOM_ERROR_RATE));
+                let bloom = BloomFilter::new(name.clone(), capacity, error_rate)
+                    .map_err(|e| e.to_string())?;
+                data_structures.create(bloom).await
+            }
+            DataStructureType::Hll { name } => {
+                data_structures.create(HyperLogLog::new(name.clone())).await
+            }
+            DataStructureType::Ts { name, retention } => {
+                data_structures
+                    .create(TimeSeries::new(name.clone(), *retention))
+                    .await
+            }
+            DataStructureType::Graph { name } => {
+                data_structures.create(Graph::new(name.clone())).await
+            }
+            DataStructureType::Fts { name, stemming } => {
+                data_structures
+                    .create(FullTextIndex::new(name.clone(), *stemming))
+                    .await
+            }
+            DataStructureType::Geo { name } => {
+                data_structures.create(GeoIndex::new(name.clone())).await
+            }
+        };
+        Ok(reply)
+    }
+
+    /// Removes the structure, if it exists.
+    async fn destroy(&self, data_structures: &DataStructures) -> String {
+        match self {
+            DataStructureType::Ctree { name, .. } => {
+                data_structures.destroy::<CharTree>(name).await
+            }
+            DataStructureType::Hmap { name } => data_structures.destroy::<HashMap>(name).await,
+            DataStructureType::Zset { name } => data_structures.destroy::<SortedSet>(name).await,
+            DataStructureType::List { name } => data_structures.remove_list(name).await,
+            DataStructureType::Set { name } => data_structures.destroy::<Set>(name).await,
+            DataStructureType::Bloom { name, .. } => {
+                data_structures.destroy::<BloomFilter>(name).await
+            }
+            DataStructureType::Hll { name } => data_structures.destroy::<HyperLogLog>(name).await,
+            DataStructureType::Ts { name, .. } => data_structures.destroy::<TimeSeries>(name).await,
+            DataStructureType::Graph { name } => data_structures.destroy::<Graph>(name).await,
+            DataStructureType::Fts { name, .. } => {
+                data_structures.destroy::<FullTextIndex>(name).await
+            }
+            DataStructureType::Geo { name } => data_structures.destroy::<GeoIndex>(name).await,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum PlatformRwOpType {
     CreateStructure(DataStructureType),
     DestroyStructure(DataStructureType),
 }
 
+/// Condition of `insertnx` and `insertxx`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum InsertCondition {
+    /// insertnx: only insert if the key has no value.
+    IfAbsent,
+    /// insertxx: only insert if the key already has a value.
+    IfPresent,
+}
+
 #[derive(Debug)]
 pub enum CtreeOpType {
     Insert {
         target: String,
         key: String,
         value: String,
+        condition: Option<InsertCondition>,
     },
     Remove {
         target: String,
@@ -70,10 +320,167 @@ pub enum CtreeOpType {
         target: String,
         key: String,
     },
+    HitWithKey {
+        target: String,
+        key: String,
+    },
+    HitAll {
+        target: String,
+        key: String,
+    },
+    Suggest {
+        target: String,
+        prefix: String,
+        k: usize,
+    },
+    Score {
+        target: String,
+        key: String,
+        score: u64,
+    },
+    Fuzzy {
+        target: String,
+        query: String,
+        max_distance: usize,
+    },
+    Match {
+        target: String,
+        pattern: String,
+    },
+    Regex {
+        target: String,
+        pattern: String,
+        limit: Option<usize>,
+    },
+    DeletePrefix {
+        target: String,
+        prefix: String,
+    },
+    CountPrefix {
+        target: String,
+        prefix: String,
+    },
+    Rename {
+        target: String,
+        old: String,
+        new: String,
+    },
+    RenamePrefix {
+        target: String,
+        old_prefix: String,
+        new_prefix: String,
+    },
+    Incr {
+        target: String,
+        key: String,
+        delta: i64,
+    },
+    IncrFloat {
+        target: String,
+        key: String,
+        delta: f64,
+    },
+    Append {
+        target: String,
+        key: String,
+        suffix: String,
+    },
+    Strlen {
+        target: String,
+        key: String,
+    },
+    GetRange {
+        target: String,
+        key: String,
+        start: i64,
+        end: i64,
+    },
+    SetRange {
+        target: String,
+        key: String,
+        offset: usize,
+        value: String,
+    },
+    GetSet {
+        target: String,
+        key: String,
+        value: String,
+    },
+    GetDel {
+        target: String,
+        key: String,
+    },
+    GetAt {
+        target: String,
+        key: String,
+        point: VersionPoint,
+    },
+    History {
+        target: String,
+        key: String,
+    },
     Scan {
         target: String,
     },
+    /// Writes the tree to a file in the server's working directory.
+    Save {
+        target: String,
+        file: String,
+    },
     List,
+    Copy {
+        source: String,
+        destination: String,
+        /// Overwrite the destination if it already exists.
+        replace: bool,
+    },
+    Merge {
+        source: String,
+        destination: String,
+        policy: MergePolicy,
+    },
+    Diff {
+        left: String,
+        right: String,
+    },
+}
+
+impl CtreeOpType {
+    /// Parses the commands working on two ctrees: copy, merge and diff.
+    fn from_cross_tree(command: &str, args: &str) -> Result<Self, RequestParserError> {
+        let (first, rest) = split_once_or_err(args, " ")?;
+        match command {
+            "copy" => {
+                let (destination, replace) = match rest.rsplit_once(' ') {
+                    Some((destination, "replace")) => (destination, true),
+                    _ => (rest, false),
+                };
+                Ok(CtreeOpType::Copy {
+                    source: first.to_owned(),
+                    destination: destination.to_owned(),
+                    replace,
+                })
+            }
+            "merge" => {
+                let (destination, policy) = match rest.split_once(' ') {
+                    Some((destination, "overwrite")) => (destination, MergePolicy::Overwrite),
+                    Some((destination, "keep")) => (destination, MergePolicy::Keep),
+                    Some(_) => return Err(RequestParserError::InvalidRequest),
+                    None => (rest, MergePolicy::Overwrite),
+                };
+                Ok(CtreeOpType::Merge {
+                    source: first.to_owned(),
+                    destination: destination.to_owned(),
+                    policy,
+                })
+            }
+            "diff" => Ok(CtreeOpType::Diff {
+                left: first.to_owned(),
+                right: rest.to_owned(),
+            }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
 }
 
 impl TryFrom<String> for CtreeOpType {
@@ -101,12 +508,18 @@ impl TryFrom<String> for CtreeOpType {
 
         let (operation, key_value) = split_once_or_err(leftover, " ")?;
         match operation {
-            "insert" => {
+            "insert" | "insertnx" | "insertxx" => {
                 let (key, value) = split_once_or_err(key_value, " ")?;
+                let condition = match operation {
+                    "insertnx" => Some(InsertCondition::IfAbsent),
+                    "insertxx" => Some(InsertCondition::IfPresent),
+                    _ => None,
+                };
                 Ok(CtreeOpType::Insert {
                     target: target.to_owned(),
                     key: key.to_owned(),
                     value: value.to_owned(),
+                    condition,
                 })
             }
             "remove" => Ok(CtreeOpType::Remove {
@@ -121,17 +534,221 @@ impl TryFrom<String> for CtreeOpType {
                 target: target.to_owned(),
                 key: key_value.to_owned(),
             }),
+            "hitk" => Ok(CtreeOpType::HitWithKey {
+                target: target.to_owned(),
+                key: key_value.to_owned(),
+            }),
+            "hitall" => Ok(CtreeOpType::HitAll {
+                target: target.to_owned(),
+                key: key_value.to_owned(),
+            }),
+            "suggest" => {
+                let (prefix, k) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::Suggest {
+                    target: target.to_owned(),
+                    prefix: prefix.to_owned(),
+                    k: parse(k)?,
+                })
+            }
+            "score" => {
+                let (key, score) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::Score {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    score: parse(score)?,
+                })
+            }
+            "delprefix" => Ok(CtreeOpType::DeletePrefix {
+                target: target.to_owned(),
+                prefix: key_value.to_owned(),
+            }),
+            "countprefix" => Ok(CtreeOpType::CountPrefix {
+                target: target.to_owned(),
+                prefix: key_value.to_owned(),
+            }),
+            "rename" => {
+                let (old, new) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::Rename {
+                    target: target.to_owned(),
+                    old: old.to_owned(),
+                    new: new.to_owned(),
+                })
+            }
+            "renameprefix" => {
+                let (old_prefix, new_prefix) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::RenamePrefix {
+                    target: target.to_owned(),
+                    old_prefix: old_prefix.to_owned(),
+                    new_prefix: new_prefix.to_owned(),
+                })
+            }
+            "match" => Ok(CtreeOpType::Match {
+                target: target.to_owned(),
+                pattern: key_value.to_owned(),
+            }),
+            "regex" => {
+                // The pattern may contain spaces, so the limit follows an explicit keyword.
+                let (pattern, limit) = match key_value.rsplit_once(" limit ") {
+                    Some((pattern, limit)) if !limit.contains(' ') => {
+                        (pattern, Some(parse(limit)?))
+                    }
+                    _ => (key_value, None),
+                };
+                Ok(CtreeOpType::Regex {
+                    target: target.to_owned(),
+                    pattern: pattern.to_owned(),
+                    limit,
+                })
+            }
+            "incr" | "decr" | "incrby" => {
+                let (key, delta) = match key_value.split_once(' ') {
+                    Some((key, delta)) => (key, parse::<i64>(delta)?),
+                    None if operation == "incrby" => {
+                        return Err(RequestParserError::InvalidRequest)
+                    }
+                    None => (key_value, 1),
+                };
+                let delta = if operation == "decr" {
+                    delta
+                        .checked_neg()
+                        .ok_or(RequestParserError::InvalidRequest)?
+                } else {
+                    delta
+                };
+                Ok(CtreeOpType::Incr {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    delta,
+                })
+            }
+            "incrbyfloat" => {
+                let (key, delta) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::IncrFloat {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    delta: delta
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|delta| delta.is_finite())
+                        .ok_or(RequestParserError::InvalidRequest)?,
+                })
+            }
+            "append" => {
+                let (key, suffix) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::Append {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    suffix: suffix.to_owned(),
+                })
+            }
+            "strlen" => Ok(CtreeOpType::Strlen {
+                target: target.to_owned(),
+                key: key_value.to_owned(),
+            }),
+            "getrange" => {
+                let (key, range) = split_once_or_err(key_value, " ")?;
+                let (start, end) = split_once_or_err(range, " ")?;
+                Ok(CtreeOpType::GetRange {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    start: parse(start)?,
+                    end: parse(end)?,
+                })
+            }
+            "setrange" => {
+                let (key, offset_value) = split_once_or_err(key_value, " ")?;
+                let (offset, value) = split_once_or_err(offset_value, " ")?;
+                Ok(CtreeOpType::SetRange {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    offset: offset
+                        .parse()
+                        .ok()
+                        .filter(|offset| *offset <= MAX_SETRANGE_OFFSET)
+                        .ok_or(RequestParserError::InvalidRequest)?,
+                    value: value.to_owned(),
+                })
+            }
+            "getset" => {
+                let (key, value) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::GetSet {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                })
+            }
+            "getdel" => Ok(CtreeOpType::GetDel {
+                target: target.to_owned(),
+                key: key_value.to_owned(),
+            }),
+            "getat" => {
+                let (key, point) = split_once_or_err(key_value, " ")?;
+                let point = match point.strip_prefix('@') {
+                    Some(timestamp) => VersionPoint::Time(parse(timestamp)?),
+                    None => VersionPoint::Seq(parse(point)?),
+                };
+                Ok(CtreeOpType::GetAt {
+                    target: target.to_owned(),
+                    key: key.to_owned(),
+                    point,
+                })
+            }
+            "history" => Ok(CtreeOpType::History {
+                target: target.to_owned(),
+                key: key_value.to_owned(),
+            }),
+            // Only plain file names, so a request can't write outside the working directory.
+            "save" if !key_value.contains(['/', '\\']) && key_value != "." && key_value != ".." => {
+                Ok(CtreeOpType::Save {
+                    target: target.to_owned(),
+                    file: key_value.to_owned(),
+                })
+            }
+            "fuzzy" => {
+                let (query, max_distance) = split_once_or_err(key_value, " ")?;
+                Ok(CtreeOpType::Fuzzy {
+                    target: target.to_owned(),
+                    query: query.to_owned(),
+                    max_distance: parse(max_distance)?,
+                })
+            }
             _ => Err(RequestParserError::InvalidRequest),
         }
     }
 }
 
+// Every structure type gets its own `<Type>Op` variant.
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug)]
 pub enum RequestToken {
     PlatformRwOp(PlatformRwOpType),
     CtreeOp(CtreeOpType),
+    HmapOp(HmapOpType),
+    ZsetOp(ZsetOpType),
+    ListOp(ListOpType),
+    SetOp(SetOpType),
+    BloomOp(BloomOpType),
+    HllOp(HllOpType),
+    TsOp(TsOpType),
+    GraphOp(GraphOpType),
+    FtsOp(FtsOpType),
+    GeoOp(GeoOpType),
+}
+/// Takes a snapshot of the ctree and releases all locks, so writers aren't blocked
+/// while a long scan or save reads it.
+async fn snapshot_ctree(platform: &Arc<RwLock<Platform>>, name: &str) -> Result<CharTree, String> {
+    let platforn_lock = platform.read().await;
+    platforn_lock
+        .with_ref(name, |ctree: &CharTree| ctree.snapshot())
+        .await
 }
+
 impl RequestToken {
+    /// Whether the request may wait for other clients before it replies.
+    pub fn is_blocking(&self) -> bool {
+        matches!(self, RequestToken::ListOp(ListOpType::BlockingPop { .. }))
+    }
+
     fn from_string(value: String) -> Result<Self, RequestParserError> {
         debug!("Received input: {}", value);
         let (root_command, leftover_str) = split_once_or_err(&value, " ")?;
@@ -144,6 +761,27 @@ impl RequestToken {
                 PlatformRwOpType::DestroyStructure(DataStructureType::try_from(leftover)?),
             )),
             "ctree" => Ok(RequestToken::CtreeOp(CtreeOpType::try_from(leftover)?)),
+            "hmap" => Ok(RequestToken::HmapOp(HmapOpType::try_from(leftover)?)),
+            "zset" => Ok(RequestToken::ZsetOp(ZsetOpType::try_from(leftover)?)),
+            "list" => Ok(RequestToken::ListOp(ListOpType::try_from(leftover)?)),
+            "set" => Ok(RequestToken::SetOp(SetOpType::try_from(leftover)?)),
+            "bloom" => Ok(RequestToken::BloomOp(BloomOpType::try_from(leftover)?)),
+            "hll" => Ok(RequestToken::HllOp(HllOpType::try_from(leftover)?)),
+            "ts" => Ok(RequestToken::TsOp(TsOpType::try_from(leftover)?)),
+            "graph" => Ok(RequestToken::GraphOp(GraphOpType::try_from(leftover)?)),
+            "fts" => Ok(RequestToken::FtsOp(FtsOpType::try_from(leftover)?)),
+            "geo" => Ok(RequestToken::GeoOp(GeoOpType::try_from(leftover)?)),
+            "sunion" | "sinter" | "sdiff" | "sunionstore" | "sinterstore" | "sdiffstore" => Ok(
+                RequestToken::SetOp(SetOpType::from_algebra(root_command, &leftover)?),
+            ),
+            "copy" | "merge" | "diff" => Ok(RequestToken::CtreeOp(CtreeOpType::from_cross_tree(
+                root_command,
+                &leftover,
+            )?)),
+            "pfcount" | "pfmerge" => Ok(RequestToken::HllOp(HllOpType::from_multi(
+                root_command,
+                &leftover,
+            )?)),
             _ => Err(RequestParserError::InvalidRequest),
         }
     }
@@ -151,104 +789,385 @@ impl RequestToken {
     pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
         debug!("Executing request: {:?}", self);
         match self {
-            //create <structure type> < structure name>
-            RequestToken::PlatformRwOp(PlatformRwOpType::CreateStructure(
-                DataStructureType::Ctree { name },
-            )) => {
+            // create <structure type> <structure name> [options]
+            RequestToken::PlatformRwOp(PlatformRwOpType::CreateStructure(structure)) => {
                 let platforn_lock = platform.write().await;
-                let data_structures_lock = platforn_lock.data_structures.write().await;
-                data_structures_lock
-                    .insert_ctree(CharTree::new(name.clone()))
-                    .await;
-                Ok(format!("Ctree {} created", name))
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                structure.create(&data_structures_lock).await
             }
             // destroy <structure type> <structure name>
-            RequestToken::PlatformRwOp(PlatformRwOpType::DestroyStructure(
-                DataStructureType::Ctree { name },
-            )) => {
+            RequestToken::PlatformRwOp(PlatformRwOpType::DestroyStructure(structure)) => {
                 let platforn_lock = platform.write().await;
-                let data_structures_lock = platforn_lock.data_structures.write().await;
-                data_structures_lock.remove_ctree(&name).await;
-                Ok(format!("Ctree {} removed", name))
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                Ok(structure.destroy(&data_structures_lock).await)
             }
-            // ctree <target> insert <key> <value>
-            RequestToken::CtreeOp(CtreeOpType::Insert { target, key, value }) => {
+            // ctree <target> insert|insertnx|insertxx <key> <value>
+            RequestToken::CtreeOp(CtreeOpType::Insert {
+                target,
+                key,
+                value,
+                condition,
+            }) => {
                 let platforn_lock = platform.write().await;
-                let data_structures_lock = platforn_lock.data_structures.write().await;
-                let ctree = data_structures_lock.get_ctree(&target).await;
-                if ctree.is_none() {
-                    return Err("Ctree not found".to_string());
-                }
-                let ctree_lock = ctree.unwrap();
-                let mut ctree_write = ctree_lock.write().await;
-                ctree_write.insert(&key, &value);
-                Ok(format!("Key {} inserted", key))
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        let inserted = match condition {
+                            None => {
+                                ctree.insert(key, value);
+                                true
+                            }
+                            Some(InsertCondition::IfAbsent) => {
+                                ctree.insert_if_absent(key, value).is_none()
+                            }
+                            Some(InsertCondition::IfPresent) => ctree.replace(key, value).is_some(),
+                        };
+                        if !inserted {
+                            return format!("Key {} not inserted", key);
+                        }
+                        format!("Key {} inserted", key)
+                    })
+                    .await
             }
             // ctree <target> remove <key>
             RequestToken::CtreeOp(CtreeOpType::Remove { target, key }) => {
                 let platforn_lock = platform.write().await;
-                let data_structures_lock = platforn_lock.data_structures.write().await;
-                let ctree = match data_structures_lock.get_ctree(&target).await {
-                    Some(ctree) => ctree,
-                    None => return Err("Ctree not found".to_string()),
-                };
-                let mut ctree_write = ctree.write().await;
-                ctree_write.deep_delete(&key);
-                Ok(format!("Key {} removed", key))
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        ctree.deep_delete(key);
+                        format!("Key {} removed", key)
+                    })
+                    .await
             }
             // ctree <target> get <key>
             RequestToken::CtreeOp(CtreeOpType::Get { target, key }) => {
                 let platforn_lock = platform.read().await;
-                let data_structures_lock = platforn_lock.data_structures.read().await;
-                let ctree = data_structures_lock.get_ctree(&target).await;
-                if ctree.is_none() {
-                    info!("Ctree {} not found", &target);
-                    return Err("Ctree not found".to_string());
-                }
-                dbg!(&ctree);
-                let ctree_lock = ctree.unwrap();
-                let ctree_read = ctree_lock.read().await;
-                let value = ctree_read.get(&key);
-                println!("Value: {:?}", value);
-                match value {
-                    Some(value) => Ok(value.clone()),
-                    None => Err("Key not found".to_string()),
-                }
+                let value = platforn_lock
+                    .with_ref(target, |ctree: &CharTree| ctree.get(key))
+                    .await?;
+                value.ok_or_else(|| "Key not found".to_string())
             }
             // ctree <target> hit <key>
             RequestToken::CtreeOp(CtreeOpType::Hit { target, key }) => {
                 let platforn_lock = platform.read().await;
-                let data_structures_lock = platforn_lock.data_structures.read().await;
-                let ctree = data_structures_lock.get_ctree(&target).await;
-                if ctree.is_none() {
-                    return Err("Ctree not found".to_string());
+                let value = platforn_lock
+                    .with_ref(target, |ctree: &CharTree| ctree.hit(key))
+                    .await?;
+                value.ok_or_else(|| "Key not found".to_string())
+            }
+            // ctree <target> hitk <key>
+            RequestToken::CtreeOp(CtreeOpType::HitWithKey { target, key }) => {
+                let platforn_lock = platform.read().await;
+                let hit = platforn_lock
+                    .with_ref(target, |ctree: &CharTree| ctree.hit_with_key(key))
+                    .await?;
+                match hit {
+                    Some((matched_key, value)) => Ok(format!("{} {}", matched_key, value)),
+                    None => Err("Key not found".to_string()),
                 }
-                let ctree_lock = ctree.unwrap();
-                let ctree_read = ctree_lock.read().await;
-                ctree_read.hit(&key);
-                Ok("Key hit".to_string())
             }
-            // ctree <target> scan
-            RequestToken::CtreeOp(CtreeOpType::Scan { target }) => {
+            // ctree <target> hitall <key>
+            RequestToken::CtreeOp(CtreeOpType::HitAll { target, key }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        format!("{:?}", ctree.hit_all(key))
+                    })
+                    .await
+            }
+            // ctree <target> suggest <prefix> <k>
+            RequestToken::CtreeOp(CtreeOpType::Suggest { target, prefix, k }) => {
                 let platforn_lock = platform.read().await;
-                let data_structures_lock = platforn_lock.data_structures.read().await;
-                let ctree = data_structures_lock.get_ctree(&target).await;
-                if ctree.is_none() {
-                    return Err("Ctree not found".to_string());
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        format!("{:?}", ctree.complete(prefix, *k))
+                    })
+                    .await
+            }
+            // ctree <target> score <key> <score>
+            RequestToken::CtreeOp(CtreeOpType::Score { target, key, score }) => {
+                let platforn_lock = platform.write().await;
+                let scored = platforn_lock
+                    .with(target, |ctree: &mut CharTree| ctree.set_score(key, *score))
+                    .await?;
+                if !scored {
+                    return Err("Key not found".to_string());
                 }
-                let ctree_lock = ctree.unwrap();
-                let ctree_read = ctree_lock.read().await;
-                let keys = ctree_read.scan();
+                Ok(format!("Key {} scored {}", key, score))
+            }
+            // ctree <target> fuzzy <query> <max distance>
+            RequestToken::CtreeOp(CtreeOpType::Fuzzy {
+                target,
+                query,
+                max_distance,
+            }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        format!("{:?}", ctree.fuzzy(query, *max_distance))
+                    })
+                    .await
+            }
+            // ctree <target> match <pattern>
+            RequestToken::CtreeOp(CtreeOpType::Match { target, pattern }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        let matches = ctree.glob(pattern).map_err(|e| e.to_string())?;
+                        Ok(format!("{:?}", matches))
+                    })
+                    .await?
+            }
+            // ctree <target> regex <pattern> [limit <n>]
+            RequestToken::CtreeOp(CtreeOpType::Regex {
+                target,
+                pattern,
+                limit,
+            }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        let matches = ctree.regex(pattern, *limit).map_err(|e| e.to_string())?;
+                        Ok(format!("{:?}", matches))
+                    })
+                    .await?
+            }
+            // ctree <target> delprefix <prefix>
+            RequestToken::CtreeOp(CtreeOpType::DeletePrefix { target, prefix }) => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        format!("{} keys removed", ctree.delete_prefix(prefix))
+                    })
+                    .await
+            }
+            // ctree <target> countprefix <prefix>
+            RequestToken::CtreeOp(CtreeOpType::CountPrefix { target, prefix }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        ctree.count_prefix(prefix).to_string()
+                    })
+                    .await
+            }
+            // ctree <target> rename <old> <new>
+            RequestToken::CtreeOp(CtreeOpType::Rename { target, old, new }) => {
+                let platforn_lock = platform.write().await;
+                let renamed = platforn_lock
+                    .with(target, |ctree: &mut CharTree| ctree.rename(old, new))
+                    .await?;
+                if !renamed {
+                    return Err("Key not found".to_string());
+                }
+                Ok(format!("Key {} renamed to {}", old, new))
+            }
+            // ctree <target> renameprefix <old prefix> <new prefix>
+            RequestToken::CtreeOp(CtreeOpType::RenamePrefix {
+                target,
+                old_prefix,
+                new_prefix,
+            }) => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        format!("{} keys moved", ctree.rename_prefix(old_prefix, new_prefix))
+                    })
+                    .await
+            }
+            // ctree <target> incr|decr|incrby <key> [n]
+            RequestToken::CtreeOp(CtreeOpType::Incr { target, key, delta }) => {
+                let platforn_lock = platform.write().await;
+                let value = platforn_lock
+                    .with(target, |ctree: &mut CharTree| ctree.incr_by(key, *delta))
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                Ok(value.to_string())
+            }
+            // ctree <target> incrbyfloat <key> <n>
+            RequestToken::CtreeOp(CtreeOpType::IncrFloat { target, key, delta }) => {
+                let platforn_lock = platform.write().await;
+                let value = platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        ctree.incr_by_float(key, *delta)
+                    })
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                Ok(value.to_string())
+            }
+            // ctree <target> append <key> <suffix>
+            RequestToken::CtreeOp(CtreeOpType::Append {
+                target,
+                key,
+                suffix,
+            }) => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        ctree.append(key, suffix).to_string()
+                    })
+                    .await
+            }
+            // ctree <target> strlen <key>
+            RequestToken::CtreeOp(CtreeOpType::Strlen { target, key }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| ctree.strlen(key).to_string())
+                    .await
+            }
+            // ctree <target> getrange <key> <start> <end>
+            RequestToken::CtreeOp(CtreeOpType::GetRange {
+                target,
+                key,
+                start,
+                end,
+            }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        ctree.get_range(key, *start, *end)
+                    })
+                    .await
+            }
+            // ctree <target> setrange <key> <offset> <value>
+            RequestToken::CtreeOp(CtreeOpType::SetRange {
+                target,
+                key,
+                offset,
+                value,
+            }) => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        ctree.set_range(key, *offset, value).to_string()
+                    })
+                    .await
+            }
+            // ctree <target> getset <key> <value>
+            RequestToken::CtreeOp(CtreeOpType::GetSet { target, key, value }) => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |ctree: &mut CharTree| {
+                        ctree.get_set(key, value).unwrap_or_default()
+                    })
+                    .await
+            }
+            // ctree <target> getdel <key>
+            RequestToken::CtreeOp(CtreeOpType::GetDel { target, key }) => {
+                let platforn_lock = platform.write().await;
+                let value = platforn_lock
+                    .with(target, |ctree: &mut CharTree| ctree.get_del(key))
+                    .await?;
+                value.ok_or_else(|| "Key not found".to_string())
+            }
+            // ctree <target> getat <key> <seq|@unix ms>
+            RequestToken::CtreeOp(CtreeOpType::GetAt { target, key, point }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        if !ctree.is_versioned() {
+                            return Err("Ctree is not versioned".to_string());
+                        }
+                        ctree
+                            .get_at(key, *point)
+                            .ok_or_else(|| "Key not found".to_string())
+                    })
+                    .await?
+            }
+            // ctree <target> history <key>
+            RequestToken::CtreeOp(CtreeOpType::History { target, key }) => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |ctree: &CharTree| {
+                        if !ctree.is_versioned() {
+                            return Err("Ctree is not versioned".to_string());
+                        }
+                        Ok(format!("{:?}", ctree.history(key)))
+                    })
+                    .await?
+            }
+            // ctree <target> scan
+            RequestToken::CtreeOp(CtreeOpType::Scan { target }) => {
+                let snapshot = snapshot_ctree(platform, target).await?;
+                let keys = snapshot.scan();
                 Ok(format!("{:?}", keys))
             }
+            // ctree <target> save <file>
+            RequestToken::CtreeOp(CtreeOpType::Save { target, file }) => {
+                let snapshot = snapshot_ctree(platform, target).await?;
+                let path = file.clone();
+                tokio::task::spawn_blocking(move || -> Result<(), String> {
+                    let mut writer = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+                    snapshot.save(&mut writer).map_err(|e| e.to_string())?;
+                    writer.flush().map_err(|e| e.to_string())
+                })
+                .await
+                .map_err(|e| e.to_string())??;
+                Ok(format!("Ctree {} saved to {}", target, file))
+            }
+            // copy <source> <destination> [replace]
+            RequestToken::CtreeOp(CtreeOpType::Copy {
+                source,
+                destination,
+                replace,
+            }) => {
+                let platforn_lock = platform.write().await;
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                let ctree = data_structures_lock.get::<CharTree>(source).await?;
+                if !replace && data_structures_lock.exists::<CharTree>(destination).await {
+                    return Err(format!("Ctree {} already exists", destination));
+                }
+                let copy = ctree.read().await.copy(destination.clone());
+                data_structures_lock.replace(copy).await;
+                Ok(format!("Ctree {} copied to {}", source, destination))
+            }
+            // merge <source> <destination> [overwrite|keep]
+            RequestToken::CtreeOp(CtreeOpType::Merge {
+                source,
+                destination,
+                policy,
+            }) => {
+                let platforn_lock = platform.write().await;
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                let source_tree = data_structures_lock.get::<CharTree>(source).await?;
+                let destination_tree = data_structures_lock.get::<CharTree>(destination).await?;
+                if Arc::ptr_eq(&source_tree, &destination_tree) {
+                    return Ok("0 keys merged".to_string());
+                }
+                let source_read = source_tree.read().await;
+                let mut destination_write = destination_tree.write().await;
+                let written = destination_write.merge(&source_read, *policy);
+                Ok(format!("{} keys merged", written))
+            }
+            // diff <left> <right>
+            RequestToken::CtreeOp(CtreeOpType::Diff { left, right }) => {
+                let platforn_lock = platform.read().await;
+                let data_structures_lock = platforn_lock.r_lock_data_structures().await;
+                let left_tree = data_structures_lock.get::<CharTree>(left).await?;
+                let right_tree = data_structures_lock.get::<CharTree>(right).await?;
+                let left_read = left_tree.read().await;
+                let diff = if Arc::ptr_eq(&left_tree, &right_tree) {
+                    left_read.diff(&left_read)
+                } else {
+                    left_read.diff(&*right_tree.read().await)
+                };
+                Ok(format!("{:?}", diff))
+            }
             // ctree list
             RequestToken::CtreeOp(CtreeOpType::List) => {
                 let platforn_lock = platform.read().await;
-                let data_structures_lock = platforn_lock.data_structures.read().await;
-                let ctrees = data_structures_lock.get_all_ctrees().await;
+                let data_structures_lock = platforn_lock.r_lock_data_structures().await;
+                let ctrees = data_structures_lock.get_all::<CharTree>().await;
                 Ok(format!("{:?}", ctrees))
             }
-            _ => Err("Invalid request".to_string()),
+            RequestToken::HmapOp(hmap_op) => hmap_op.execute(platform).await,
+            RequestToken::ZsetOp(zset_op) => zset_op.execute(platform).await,
+            RequestToken::ListOp(list_op) => list_op.execute(platform).await,
+            RequestToken::SetOp(set_op) => set_op.execute(platform).await,
+            RequestToken::BloomOp(bloom_op) => bloom_op.execute(platform).await,
+            RequestToken::HllOp(hll_op) => hll_op.execute(platform).await,
+            RequestToken::TsOp(ts_op) => ts_op.execute(platform).await,
+            RequestToken::GraphOp(graph_op) => graph_op.execute(platform).await,
+            RequestToken::FtsOp(fts_op) => fts_op.execute(platform).await,
+            RequestToken::GeoOp(geo_op) => geo_op.execute(platform).await,
         }
     }
 }
@@ -283,11 +1202,257 @@ mod tests {
         assert!(result.is_ok());
         match result.unwrap() {
             RequestToken::PlatformRwOp(PlatformRwOpType::CreateStructure(
-                DataStructureType::Ctree { name },
+                DataStructureType::Ctree {
+                    name,
+                    history_limit,
+                },
+            )) => {
+                assert_eq!(name, "my_tree".to_string());
+                assert_eq!(history_limit, None);
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_incr() {
+        let request = "ctree counters decr hits/a".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Incr { key, delta, .. }) => {
+                assert_eq!(key, "hits/a".to_string());
+                assert_eq!(delta, -1);
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree counters incrby hits/a 10".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Incr { delta, .. }) => assert_eq!(delta, 10),
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree counters incrby hits/a".to_string();
+        assert!(RequestToken::from_string(request).is_err());
+        let request = "ctree counters incrbyfloat hits/a inf".to_string();
+        assert!(RequestToken::from_string(request).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_string_ops() {
+        let request = "ctree my_tree getrange key -5 -1".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::GetRange {
+                key, start, end, ..
+            }) => {
+                assert_eq!(key, "key".to_string());
+                assert_eq!((start, end), (-5, -1));
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree my_tree setrange key 3 two words".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::SetRange { offset, value, .. }) => {
+                assert_eq!(offset, 3);
+                assert_eq!(value, "two words".to_string());
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree my_tree setrange key -1 x".to_string();
+        assert!(RequestToken::from_string(request).is_err());
+        let request = format!("ctree my_tree setrange key {} x", MAX_SETRANGE_OFFSET + 1);
+        assert!(RequestToken::from_string(request).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_bloom() {
+        let request = "create bloom seen 1000000 0.001".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::PlatformRwOp(PlatformRwOpType::CreateStructure(
+                DataStructureType::Bloom { name, parameters },
+            )) => {
+                assert_eq!(name, "seen".to_string());
+                assert_eq!(parameters, Some((1000000, 0.001)));
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "create bloom seen 1000000".to_string();
+        assert!(RequestToken::from_string(request).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_versioned() {
+        let request = "create ctree audit versioned 4".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::PlatformRwOp(PlatformRwOpType::CreateStructure(
+                DataStructureType::Ctree {
+                    name,
+                    history_limit,
+                },
+            )) => {
+                assert_eq!(name, "audit".to_string());
+                assert_eq!(history_limit, Some(4));
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree audit getat key @1700000000000".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::GetAt { key, point, .. }) => {
+                assert_eq!(key, "key".to_string());
+                assert_eq!(point, VersionPoint::Time(1700000000000));
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "create ctree audit snapshot".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::PlatformRwOp(PlatformRwOpType::CreateStructure(
+                DataStructureType::Ctree {
+                    name,
+                    history_limit,
+                },
             )) => {
-                assert_eq!(name, "my_tree".to_string())
+                assert_eq!(name, "audit snapshot".to_string());
+                assert_eq!(history_limit, None);
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_conditional_insert() {
+        let request = "ctree locks insertnx job owner 1".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Insert {
+                key,
+                value,
+                condition,
+                ..
+            }) => {
+                assert_eq!(key, "job".to_string());
+                assert_eq!(value, "owner 1".to_string());
+                assert_eq!(condition, Some(InsertCondition::IfAbsent));
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree locks insertxx job owner 2".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Insert { condition, .. }) => {
+                assert_eq!(condition, Some(InsertCondition::IfPresent));
+            }
+            _ => panic!("unexpected result"),
+        }
+        // Values ending like a condition are stored as they are.
+        let request = "ctree locks insert job hello xx".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Insert {
+                value, condition, ..
+            }) => {
+                assert_eq!(value, "hello xx".to_string());
+                assert_eq!(condition, None);
+            }
+            _ => panic!("unexpected result"),
+        }
+    }
+
+    #[test]
+    fn test_tokenize_merge() {
+        let request = "merge staging production keep".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Merge {
+                source,
+                destination,
+                policy,
+            }) => {
+                assert_eq!(source, "staging".to_string());
+                assert_eq!(destination, "production".to_string());
+                assert_eq!(policy, MergePolicy::Keep);
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "merge staging production replace".to_string();
+        assert!(RequestToken::from_string(request).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_save() {
+        let request = "ctree my_tree save my_tree.ctree".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Save { target, file }) => {
+                assert_eq!(target, "my_tree".to_string());
+                assert_eq!(file, "my_tree.ctree".to_string());
             }
             _ => panic!("unexpected result"),
         }
+        for file in ["../my_tree", "/tmp/my_tree", "..", "dir\\my_tree"] {
+            let request = format!("ctree my_tree save {}", file);
+            assert!(RequestToken::from_string(request).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create ctree staging",
+            "create ctree production",
+            "ctree staging insert a 1",
+            "ctree production insert a 2",
+        ] {
+            RequestToken::from_string(request.to_string())
+                .unwrap()
+                .execute(&platform)
+                .await
+                .unwrap();
+        }
+        let request = |request: &str| RequestToken::from_string(request.to_string()).unwrap();
+        assert_eq!(
+            request("copy staging production").execute(&platform).await,
+            Err("Ctree production already exists".to_string())
+        );
+        request("copy staging production replace")
+            .execute(&platform)
+            .await
+            .unwrap();
+        assert_eq!(
+            request("ctree production get a").execute(&platform).await,
+            Ok("1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tokenize_suggest() {
+        let request = "ctree my_tree suggest ab 5".to_string();
+        match RequestToken::from_string(request).unwrap() {
+            RequestToken::CtreeOp(CtreeOpType::Suggest { target, prefix, k }) => {
+                assert_eq!(target, "my_tree".to_string());
+                assert_eq!(prefix, "ab".to_string());
+                assert_eq!(k, 5);
+            }
+            _ => panic!("unexpected result"),
+        }
+        let request = "ctree my_tree suggest ab five".to_string();
+        assert!(RequestToken::from_string(request).is_err());
+    }
+
+    #[test]
+    fn test_tokenize_regex() {
+        for (request, expected_pattern, expected_limit) in [
+            ("ctree t regex a.*", "a.*", None),
+            ("ctree t regex a.* limit 10", "a.*", Some(10)),
+            ("ctree t regex hello world", "hello world", None),
+            ("ctree t regex order 42", "order 42", None),
+            (
+                "ctree t regex hello [a-z]+ limit 3",
+                "hello [a-z]+",
+                Some(3),
+            ),
+        ] {
+            match RequestToken::from_string(request.to_string()).unwrap() {
+                RequestToken::CtreeOp(CtreeOpType::Regex { pattern, limit, .. }) => {
+                    assert_eq!(pattern, expected_pattern.to_string());
+                    assert_eq!(limit, expected_limit);
+                }
+                _ => panic!("unexpected result"),
+            }
+        }
+        let request = "ctree t regex a.* limit ten".to_string();
+        assert!(RequestToken::from_string(request).is_err());
     }
 }
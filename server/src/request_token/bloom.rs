@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use common::dsa::bloom::BloomFilter;
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum BloomOpType {
+    Add { target: String, item: String },
+    Exists { target: String, item: String },
+    MultiAdd { target: String, items: Vec<String> },
+    MultiExists { target: String, items: Vec<String> },
+    Info { target: String },
+}
+
+impl TryFrom<String> for BloomOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("BloomOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        if leftover == "binfo" {
+            return Ok(BloomOpType::Info { target });
+        }
+
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        let items = || args.split_whitespace().map(str::to_owned).collect();
+        match operation {
+            "badd" => Ok(BloomOpType::Add {
+                target,
+                item: args.to_owned(),
+            }),
+            "bexists" => Ok(BloomOpType::Exists {
+                target,
+                item: args.to_owned(),
+            }),
+            "bmadd" => Ok(BloomOpType::MultiAdd {
+                target,
+                items: items(),
+            }),
+            "bmexists" => Ok(BloomOpType::MultiExists {
+                target,
+                items: items(),
+            }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl BloomOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing bloom request: {:?}", self);
+        match self {
+            // bloom <target> badd <item>
+            BloomOpType::Add { target, item } => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |bloom: &mut BloomFilter| {
+                        bloom.add(item).to_string()
+                    })
+                    .await
+            }
+            // bloom <target> bexists <item>
+            BloomOpType::Exists { target, item } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |bloom: &BloomFilter| {
+                        bloom.contains(item).to_string()
+                    })
+                    .await
+            }
+            // bloom <target> bmadd <item> [<item> ...]
+            BloomOpType::MultiAdd { target, items } => {
+                let platforn_lock = platform.write().await;
+                let items: Vec<&str> = items.iter().map(String::as_str).collect();
+                platforn_lock
+                    .with(target, |bloom: &mut BloomFilter| {
+                        format!("{:?}", bloom.add_many(&items))
+                    })
+                    .await
+            }
+            // bloom <target> bmexists <item> [<item> ...]
+            BloomOpType::MultiExists { target, items } => {
+                let platforn_lock = platform.read().await;
+                let items: Vec<&str> = items.iter().map(String::as_str).collect();
+                platforn_lock
+                    .with_ref(target, |bloom: &BloomFilter| {
+                        format!("{:?}", bloom.contains_many(&items))
+                    })
+                    .await
+            }
+            // bloom <target> binfo
+            BloomOpType::Info { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |bloom: &BloomFilter| format!("{:?}", bloom.info()))
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_bloom() {
+        match BloomOpType::try_from("seen bmexists a b  c".to_string()).unwrap() {
+            BloomOpType::MultiExists { target, items } => {
+                assert_eq!(target, "seen".to_string());
+                assert_eq!(items, vec!["a", "b", "c"]);
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(matches!(
+            BloomOpType::try_from("seen binfo".to_string()),
+            Ok(BloomOpType::Info { .. })
+        ));
+        assert!(BloomOpType::try_from("seen bdel a".to_string()).is_err());
+    }
+}
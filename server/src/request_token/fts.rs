@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use common::dsa::full_text::FullTextIndex;
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum FtsOpType {
+    Add {
+        target: String,
+        id: String,
+        text: String,
+    },
+    Del {
+        target: String,
+        id: String,
+    },
+    Search {
+        target: String,
+        query: String,
+    },
+}
+
+impl TryFrom<String> for FtsOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("FtsOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        match operation {
+            "ftadd" => {
+                let (id, text) = split_once_or_err(args, " ")?;
+                Ok(FtsOpType::Add {
+                    target,
+                    id: id.to_owned(),
+                    text: text.to_owned(),
+                })
+            }
+            "ftdel" => Ok(FtsOpType::Del {
+                target,
+                id: args.to_owned(),
+            }),
+            "ftsearch" => Ok(FtsOpType::Search {
+                target,
+                query: args.to_owned(),
+            }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl FtsOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing fts request: {:?}", self);
+        match self {
+            // fts <target> ftadd <id> <text>
+            FtsOpType::Add { target, id, text } => {
+                let platforn_lock = platform.write().await;
+                let added = platforn_lock
+                    .with(target, |index: &mut FullTextIndex| index.add(id, text))
+                    .await?;
+                if !added {
+                    return Ok(format!("Document {} updated", id));
+                }
+                Ok(format!("Document {} added", id))
+            }
+            // fts <target> ftdel <id>
+            FtsOpType::Del { target, id } => {
+                let platforn_lock = platform.write().await;
+                let removed = platforn_lock
+                    .with(target, |index: &mut FullTextIndex| index.remove(id))
+                    .await?;
+                if !removed {
+                    return Err("Document not found".to_string());
+                }
+                Ok(format!("Document {} removed", id))
+            }
+            // fts <target> ftsearch <query>
+            FtsOpType::Search { target, query } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |index: &FullTextIndex| {
+                        let results = index.search(query).map_err(|e| e.to_string())?;
+                        let ids: Vec<&str> = results.into_iter().map(|(id, _)| id).collect();
+                        Ok(format!("{:?}", ids))
+                    })
+                    .await?
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_fts() {
+        match FtsOpType::try_from("docs ftadd readme Girios is a database".to_string()).unwrap() {
+            FtsOpType::Add { target, id, text } => {
+                assert_eq!(target, "docs".to_string());
+                assert_eq!(id, "readme".to_string());
+                assert_eq!(text, "Girios is a database".to_string());
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(FtsOpType::try_from("docs ftadd readme".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create fts docs stem",
+            "fts docs ftadd 1 Trees and graphs",
+            "fts docs ftadd 2 A tree of words",
+            "fts docs ftadd 3 Sorted sets",
+        ] {
+            run(&platform, request).await.unwrap();
+        }
+        assert_eq!(
+            run(&platform, "fts docs ftsearch tree OR sets")
+                .await
+                .unwrap(),
+            r#"["3", "1", "2"]"#
+        );
+        assert_eq!(
+            run(&platform, "fts docs ftsearch \"tree of\"")
+                .await
+                .unwrap(),
+            r#"["2"]"#
+        );
+        assert_eq!(
+            run(&platform, "fts docs ftsearch (tree").await,
+            Err("query has unbalanced parentheses".to_string())
+        );
+        run(&platform, "fts docs ftdel 2").await.unwrap();
+        assert_eq!(
+            run(&platform, "fts docs ftsearch tree NOT graph")
+                .await
+                .unwrap(),
+            "[]"
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use std::sync::Arc;
+
+use common::dsa::geo::{GeoIndex, Shape, Unit};
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{parse, split_once_or_err, RequestParserError};
+
+/// Center of a search.
+#[derive(Debug, PartialEq)]
+pub enum Origin {
+    Member(String),
+    Position(f64, f64),
+}
+
+#[derive(Debug)]
+pub enum GeoOpType {
+    Add {
+        target: String,
+        longitude: f64,
+        latitude: f64,
+        member: String,
+    },
+    Pos {
+        target: String,
+        member: String,
+    },
+    Dist {
+        target: String,
+        from: String,
+        to: String,
+        unit: Unit,
+    },
+    /// Searches members around the origin, the shape being in meters.
+    Search {
+        target: String,
+        origin: Origin,
+        shape: Shape,
+        unit: Unit,
+    },
+}
+
+fn parse_unit(value: &str) -> Result<Unit, RequestParserError> {
+    match value {
+        "m" => Ok(Unit::Meters),
+        "km" => Ok(Unit::Kilometers),
+        "mi" => Ok(Unit::Miles),
+        "ft" => Ok(Unit::Feet),
+        _ => Err(RequestParserError::InvalidRequest),
+    }
+}
+
+impl TryFrom<String> for GeoOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("GeoOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        let args: Vec<&str> = args.split(' ').collect();
+        match (operation, args.as_slice()) {
+            ("geoadd", [longitude, latitude, member]) => Ok(GeoOpType::Add {
+                target,
+                longitude: parse(longitude)?,
+                latitude: parse(latitude)?,
+                member: member.to_string(),
+            }),
+            ("geopos", [member]) => Ok(GeoOpType::Pos {
+                target,
+                member: member.to_string(),
+            }),
+            ("geodist", [from, to, unit @ ..]) if unit.len() <= 1 => Ok(GeoOpType::Dist {
+                target,
+                from: from.to_string(),
+                to: to.to_string(),
+                unit: match unit.first() {
+                    Some(unit) => parse_unit(unit)?,
+                    None => Unit::Meters,
+                },
+            }),
+            ("georadius", [longitude, latitude, radius, unit]) => {
+                let unit = parse_unit(unit)?;
+                Ok(GeoOpType::Search {
+                    target,
+                    origin: Origin::Position(parse(longitude)?, parse(latitude)?),
+                    shape: Shape::Radius(parse::<f64>(radius)? * unit.meters()),
+                    unit,
+                })
+            }
+            ("geosearch", args) => {
+                let (origin, args) = match args {
+                    ["frommember", member, args @ ..] => (Origin::Member(member.to_string()), args),
+                    ["fromlonlat", longitude, latitude, args @ ..] => {
+                        (Origin::Position(parse(longitude)?, parse(latitude)?), args)
+                    }
+                    _ => return Err(RequestParserError::InvalidRequest),
+                };
+                let (shape, unit) = match args {
+                    ["byradius", radius, unit] => {
+                        let unit = parse_unit(unit)?;
+                        (Shape::Radius(parse::<f64>(radius)? * unit.meters()), unit)
+                    }
+                    ["bybox", width, height, unit] => {
+                        let unit = parse_unit(unit)?;
+                        let shape = Shape::Box {
+                            width: parse::<f64>(width)? * unit.meters(),
+                            height: parse::<f64>(height)? * unit.meters(),
+                        };
+                        (shape, unit)
+                    }
+                    _ => return Err(RequestParserError::InvalidRequest),
+                };
+                Ok(GeoOpType::Search {
+                    target,
+                    origin,
+                    shape,
+                    unit,
+                })
+            }
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+/// Rounds a distance in meters to 4 decimals of the unit.
+fn in_unit(meters: f64, unit: Unit) -> f64 {
+    (meters / unit.meters() * 10_000.0).round() / 10_000.0
+}
+
+impl GeoOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing geo request: {:?}", self);
+        match self {
+            // geo <target> geoadd <longitude> <latitude> <member>
+            GeoOpType::Add {
+                target,
+                longitude,
+                latitude,
+                member,
+            } => {
+                let platforn_lock = platform.write().await;
+                let added = platforn_lock
+                    .with(target, |geo: &mut GeoIndex| {
+                        geo.add(member, *longitude, *latitude)
+                    })
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                if !added {
+                    return Ok(format!("Member {} moved", member));
+                }
+                Ok(format!("Member {} added", member))
+            }
+            // geo <target> geopos <member>
+            GeoOpType::Pos { target, member } => {
+                let platforn_lock = platform.read().await;
+                let position = platforn_lock
+                    .with_ref(target, |geo: &GeoIndex| geo.position(member))
+                    .await?;
+                match position {
+                    Some(position) => Ok(format!("{:?}", position)),
+                    None => Err("Member not found".to_string()),
+                }
+            }
+            // geo <target> geodist <member> <member> [m|km|mi|ft]
+            GeoOpType::Dist {
+                target,
+                from,
+                to,
+                unit,
+            } => {
+                let platforn_lock = platform.read().await;
+                let distance = platforn_lock
+                    .with_ref(target, |geo: &GeoIndex| geo.distance(from, to))
+                    .await?;
+                match distance {
+                    Some(distance) => Ok(format!("{:.4}", distance / unit.meters())),
+                    None => Err("Member not found".to_string()),
+                }
+            }
+            // geo <target> georadius <longitude> <latitude> <radius> <m|km|mi|ft>
+            // geo <target> geosearch <frommember <member>|fromlonlat <longitude> <latitude>>
+            //     <byradius <radius>|bybox <width> <height>> <m|km|mi|ft>
+            GeoOpType::Search {
+                target,
+                origin,
+                shape,
+                unit,
+            } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |geo: &GeoIndex| {
+                        let center = match origin {
+                            Origin::Member(member) => match geo.position(member) {
+                                Some(position) => position,
+                                None => return Err("Member not found".to_string()),
+                            },
+                            Origin::Position(longitude, latitude) => (*longitude, *latitude),
+                        };
+                        let results: Vec<(String, f64)> = geo
+                            .search(center, *shape)
+                            .map_err(|e| e.to_string())?
+                            .into_iter()
+                            .map(|(member, distance)| (member, in_unit(distance, *unit)))
+                            .collect();
+                        Ok(format!("{:?}", results))
+                    })
+                    .await?
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_geo() {
+        match GeoOpType::try_from("stores geosearch frommember a bybox 2 4 km".to_string()).unwrap()
+        {
+            GeoOpType::Search {
+                target,
+                origin,
+                shape,
+                unit,
+            } => {
+                assert_eq!(target, "stores".to_string());
+                assert_eq!(origin, Origin::Member("a".to_string()));
+                assert_eq!(
+                    shape,
+                    Shape::Box {
+                        width: 2000.0,
+                        height: 4000.0
+                    }
+                );
+                assert_eq!(unit, Unit::Kilometers);
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(matches!(
+            GeoOpType::try_from("stores geodist a b".to_string()),
+            Ok(GeoOpType::Dist {
+                unit: Unit::Meters,
+                ..
+            })
+        ));
+        assert!(GeoOpType::try_from("stores georadius 1 2 3 yd".to_string()).is_err());
+        assert!(
+            GeoOpType::try_from("stores geosearch fromlonlat 1 2 byradius 3".to_string()).is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create geo sicily",
+            "geo sicily geoadd 13.361389 38.115556 palermo",
+            "geo sicily geoadd 15.087269 37.502669 catania",
+        ] {
+            run(&platform, request).await.unwrap();
+        }
+        assert_eq!(
+            run(&platform, "geo sicily geodist palermo catania km")
+                .await
+                .unwrap(),
+            "166.2742"
+        );
+        assert_eq!(
+            run(&platform, "geo sicily georadius 15 37 200 km")
+                .await
+                .unwrap(),
+            r#"[("catania", 56.4413), ("palermo", 190.4424)]"#
+        );
+        assert_eq!(
+            run(
+                &platform,
+                "geo sicily geosearch frommember palermo byradius 100 km"
+            )
+            .await
+            .unwrap(),
+            r#"[("palermo", 0.0)]"#
+        );
+        assert_eq!(
+            run(&platform, "geo sicily geoadd 0 90 pole").await,
+            Err("invalid longitude,latitude pair 0,90".to_string())
+        );
+    }
+}
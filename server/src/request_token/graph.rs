@@ -0,0 +1,292 @@
+use std::sync::Arc;
+
+use common::dsa::graph::Graph;
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{parse, split_once_or_err, RequestParserError};
+
+/// Weight of edges added without one, so shortest paths count the edges.
+const DEFAULT_EDGE_WEIGHT: f64 = 1.0;
+
+#[derive(Debug)]
+pub enum GraphOpType {
+    AddNode {
+        target: String,
+        node: String,
+    },
+    AddEdge {
+        target: String,
+        from: String,
+        to: String,
+        weight: f64,
+        label: Option<String>,
+    },
+    DelNode {
+        target: String,
+        node: String,
+    },
+    Neighbors {
+        target: String,
+        node: String,
+    },
+    Bfs {
+        target: String,
+        node: String,
+    },
+    ShortestPath {
+        target: String,
+        from: String,
+        to: String,
+    },
+}
+
+/// Parses `<from> <to> [<weight>] [label=<label>]`, the label taking the rest of the line.
+fn parse_add_edge(target: String, args: &str) -> Result<GraphOpType, RequestParserError> {
+    let mut parts = args.splitn(3, ' ');
+    let from = parts.next().unwrap_or_default();
+    let to = parts.next().ok_or(RequestParserError::InvalidRequest)?;
+    let mut rest = parts.next().unwrap_or_default();
+    let weight = if rest.is_empty() || rest.starts_with("label=") {
+        DEFAULT_EDGE_WEIGHT
+    } else {
+        let (weight, after) = rest.split_once(' ').unwrap_or((rest, ""));
+        rest = after;
+        parse(weight)?
+    };
+    let label = match rest {
+        "" => None,
+        rest => match rest.strip_prefix("label=") {
+            Some(label) if !label.is_empty() => Some(label.to_owned()),
+            _ => return Err(RequestParserError::InvalidRequest),
+        },
+    };
+    Ok(GraphOpType::AddEdge {
+        target,
+        from: from.to_owned(),
+        to: to.to_owned(),
+        weight,
+        label,
+    })
+}
+
+impl TryFrom<String> for GraphOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("GraphOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        if operation == "addedge" {
+            return parse_add_edge(target, args);
+        }
+        let args: Vec<String> = args.split(' ').map(str::to_owned).collect();
+        match (operation, args.as_slice()) {
+            ("addnode", [node]) => Ok(GraphOpType::AddNode {
+                target,
+                node: node.clone(),
+            }),
+            ("delnode", [node]) => Ok(GraphOpType::DelNode {
+                target,
+                node: node.clone(),
+            }),
+            ("neighbors", [node]) => Ok(GraphOpType::Neighbors {
+                target,
+                node: node.clone(),
+            }),
+            ("bfs", [node]) => Ok(GraphOpType::Bfs {
+                target,
+                node: node.clone(),
+            }),
+            ("shortestpath", [from, to]) => Ok(GraphOpType::ShortestPath {
+                target,
+                from: from.clone(),
+                to: to.clone(),
+            }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl GraphOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing graph request: {:?}", self);
+        match self {
+            // graph <target> addnode <node>
+            GraphOpType::AddNode { target, node } => {
+                let platforn_lock = platform.write().await;
+                let added = platforn_lock
+                    .with(target, |graph: &mut Graph| graph.add_node(node))
+                    .await?;
+                if !added {
+                    return Ok(format!("Node {} already present", node));
+                }
+                Ok(format!("Node {} added", node))
+            }
+            // graph <target> addedge <from> <to> [<weight>] [label=<label>]
+            GraphOpType::AddEdge {
+                target,
+                from,
+                to,
+                weight,
+                label,
+            } => {
+                let platforn_lock = platform.write().await;
+                let added = platforn_lock
+                    .with(target, |graph: &mut Graph| {
+                        graph.add_edge(from, to, *weight, label.clone())
+                    })
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                if !added {
+                    return Ok(format!("Edge {} -> {} updated", from, to));
+                }
+                Ok(format!("Edge {} -> {} added", from, to))
+            }
+            // graph <target> delnode <node>
+            GraphOpType::DelNode { target, node } => {
+                let platforn_lock = platform.write().await;
+                let removed = platforn_lock
+                    .with(target, |graph: &mut Graph| graph.remove_node(node))
+                    .await?;
+                if !removed {
+                    return Err("Node not found".to_string());
+                }
+                Ok(format!("Node {} removed", node))
+            }
+            // graph <target> neighbors <node>
+            GraphOpType::Neighbors { target, node } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |graph: &Graph| {
+                        let neighbors = match graph.neighbors(node) {
+                            Some(neighbors) => neighbors,
+                            None => return Err("Node not found".to_string()),
+                        };
+                        let neighbors: Vec<(&str, f64, Option<&str>)> = neighbors
+                            .into_iter()
+                            .map(|(to, edge)| (to, edge.weight, edge.label.as_deref()))
+                            .collect();
+                        Ok(format!("{:?}", neighbors))
+                    })
+                    .await?
+            }
+            // graph <target> bfs <node>
+            GraphOpType::Bfs { target, node } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |graph: &Graph| match graph.bfs(node) {
+                        Some(order) => Ok(format!("{:?}", order)),
+                        None => Err("Node not found".to_string()),
+                    })
+                    .await?
+            }
+            // graph <target> shortestpath <from> <to>
+            GraphOpType::ShortestPath { target, from, to } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |graph: &Graph| {
+                        match graph.shortest_path(from, to).map_err(|e| e.to_string())? {
+                            Some(path) => Ok(format!("{:?}", path)),
+                            None => Err("No path found".to_string()),
+                        }
+                    })
+                    .await?
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_graph() {
+        match GraphOpType::try_from("routes addedge a b 2.5 label=fast lane".to_string()).unwrap() {
+            GraphOpType::AddEdge {
+                target,
+                from,
+                to,
+                weight,
+                label,
+            } => {
+                assert_eq!(target, "routes".to_string());
+                assert_eq!((from.as_str(), to.as_str()), ("a", "b"));
+                assert_eq!(weight, 2.5);
+                assert_eq!(label, Some("fast lane".to_string()));
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(matches!(
+            GraphOpType::try_from("routes addedge a b".to_string()),
+            Ok(GraphOpType::AddEdge { weight, label: None, .. }) if weight == DEFAULT_EDGE_WEIGHT
+        ));
+        assert!(matches!(
+            GraphOpType::try_from("routes addedge a b label=walk".to_string()),
+            Ok(GraphOpType::AddEdge { weight, label: Some(label), .. })
+                if weight == DEFAULT_EDGE_WEIGHT && label == "walk"
+        ));
+        assert!(GraphOpType::try_from("routes addedge a b x".to_string()).is_err());
+        assert!(GraphOpType::try_from("routes addedge a b 2 fast".to_string()).is_err());
+        assert!(GraphOpType::try_from("routes addedge a b label=".to_string()).is_err());
+        assert!(GraphOpType::try_from("routes addedge a".to_string()).is_err());
+        assert!(GraphOpType::try_from("routes shortestpath a".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_traversal() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create graph routes",
+            "graph routes addnode a",
+            "graph routes addnode b",
+            "graph routes addnode c",
+            "graph routes addedge a b 5",
+            "graph routes addedge a c 1 label=walk",
+            "graph routes addedge c b",
+        ] {
+            run(&platform, request).await.unwrap();
+        }
+        assert_eq!(
+            run(&platform, "graph routes neighbors a").await.unwrap(),
+            r#"[("b", 5.0, None), ("c", 1.0, Some("walk"))]"#
+        );
+        assert_eq!(
+            run(&platform, "graph routes bfs a").await.unwrap(),
+            r#"["a", "b", "c"]"#
+        );
+        assert_eq!(
+            run(&platform, "graph routes shortestpath a b")
+                .await
+                .unwrap(),
+            r#"(2.0, ["a", "c", "b"])"#
+        );
+        assert_eq!(
+            run(&platform, "graph routes addedge a d").await,
+            Err("node d not found".to_string())
+        );
+        run(&platform, "graph routes delnode c").await.unwrap();
+        assert_eq!(
+            run(&platform, "graph routes shortestpath a b")
+                .await
+                .unwrap(),
+            r#"(5.0, ["a", "b"])"#
+        );
+        assert_eq!(
+            run(&platform, "graph routes shortestpath b a").await,
+            Err("No path found".to_string())
+        );
+    }
+}
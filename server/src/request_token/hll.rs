@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use common::dsa::hyperloglog::HyperLogLog;
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum HllOpType {
+    Add {
+        target: String,
+        items: Vec<String>,
+    },
+    /// Counts the distinct items added to any of the hyperloglogs.
+    Count {
+        targets: Vec<String>,
+    },
+    /// Merges the sources into the destination, creating it if needed.
+    Merge {
+        destination: String,
+        sources: Vec<String>,
+    },
+}
+
+impl HllOpType {
+    /// Parses the commands working on several hyperloglogs: pfcount and pfmerge.
+    pub fn from_multi(command: &str, args: &str) -> Result<Self, RequestParserError> {
+        let store = match command {
+            "pfcount" => false,
+            "pfmerge" => true,
+            _ => return Err(RequestParserError::InvalidRequest),
+        };
+        let mut names = args.split_whitespace().map(str::to_owned);
+        let destination = if store { names.next() } else { None };
+        let sources: Vec<String> = names.collect();
+        if sources.is_empty() {
+            return Err(RequestParserError::InvalidRequest);
+        }
+        match destination {
+            Some(destination) => Ok(HllOpType::Merge {
+                destination,
+                sources,
+            }),
+            None => Ok(HllOpType::Count { targets: sources }),
+        }
+    }
+}
+
+impl TryFrom<String> for HllOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("HllOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        if leftover == "pfcount" {
+            return Ok(HllOpType::Count {
+                targets: vec![target.to_owned()],
+            });
+        }
+        let (operation, items) = split_once_or_err(leftover, " ")?;
+        match operation {
+            "pfadd" => Ok(HllOpType::Add {
+                target: target.to_owned(),
+                items: items.split_whitespace().map(str::to_owned).collect(),
+            }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl HllOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing hll request: {:?}", self);
+        match self {
+            // hll <target> pfadd <item> [<item> ...]
+            HllOpType::Add { target, items } => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |hll: &mut HyperLogLog| {
+                        let mut changed = false;
+                        for item in items {
+                            changed |= hll.add(item);
+                        }
+                        changed.to_string()
+                    })
+                    .await
+            }
+            // hll <target> pfcount
+            // pfcount <hll> [<hll> ...]
+            HllOpType::Count { targets } => {
+                let platforn_lock = platform.read().await;
+                let data_structures_lock = platforn_lock.r_lock_data_structures().await;
+                let guards = data_structures_lock
+                    .read_many::<HyperLogLog>(targets)
+                    .await?;
+                let mut hlls = guards.values();
+                let mut union = match hlls.next() {
+                    Some(first) if guards.len() == 1 => return Ok(first.count().to_string()),
+                    Some(first) => (**first).clone(),
+                    None => return Ok("0".to_string()),
+                };
+                for hll in hlls {
+                    union.merge(hll);
+                }
+                Ok(union.count().to_string())
+            }
+            // pfmerge <destination> <hll> [<hll> ...]
+            HllOpType::Merge {
+                destination,
+                sources,
+            } => {
+                let platforn_lock = platform.write().await;
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                let mut merged = data_structures_lock
+                    .with_ref(destination, |hll: &HyperLogLog| hll.clone())
+                    .await
+                    .unwrap_or_else(|_| HyperLogLog::new(destination.clone()));
+                {
+                    let guards = data_structures_lock
+                        .read_many::<HyperLogLog>(sources)
+                        .await?;
+                    for hll in guards.values() {
+                        merged.merge(hll);
+                    }
+                }
+                let count = merged.count();
+                data_structures_lock.replace(merged).await;
+                Ok(count.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_hll() {
+        match HllOpType::from_multi("pfmerge", "all a b").unwrap() {
+            HllOpType::Merge {
+                destination,
+                sources,
+            } => {
+                assert_eq!(destination, "all".to_string());
+                assert_eq!(sources, vec!["a", "b"]);
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(HllOpType::from_multi("pfmerge", "all").is_err());
+        assert!(HllOpType::from_multi("pfcount", "").is_err());
+        assert!(matches!(
+            HllOpType::try_from("visits pfcount".to_string()),
+            Ok(HllOpType::Count { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_count_and_merge() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create hll a",
+            "create hll b",
+            "hll a pfadd x y",
+            "hll b pfadd y z",
+        ] {
+            run(&platform, request).await.unwrap();
+        }
+        assert_eq!(run(&platform, "hll a pfadd x").await.unwrap(), "false");
+        assert_eq!(run(&platform, "hll a pfcount").await.unwrap(), "2");
+        assert_eq!(run(&platform, "pfcount a b a").await.unwrap(), "3");
+        assert_eq!(run(&platform, "pfmerge all a b").await.unwrap(), "3");
+        assert_eq!(run(&platform, "hll all pfcount").await.unwrap(), "3");
+        assert_eq!(
+            run(&platform, "pfcount a missing").await,
+            Err("Hll not found".to_string())
+        );
+    }
+}
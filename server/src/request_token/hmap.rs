@@ -0,0 +1,160 @@
+use std::sync::Arc;
+
+use common::dsa::hash_map::HashMap;
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum HmapOpType {
+    Set {
+        target: String,
+        key: String,
+        value: String,
+    },
+    Get {
+        target: String,
+        key: String,
+    },
+    Del {
+        target: String,
+        key: String,
+    },
+    Exists {
+        target: String,
+        key: String,
+    },
+    Len {
+        target: String,
+    },
+    Keys {
+        target: String,
+    },
+    Scan {
+        target: String,
+    },
+}
+
+impl TryFrom<String> for HmapOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("HmapOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        match leftover {
+            "len" => return Ok(HmapOpType::Len { target }),
+            "keys" => return Ok(HmapOpType::Keys { target }),
+            "scan" => return Ok(HmapOpType::Scan { target }),
+            _ => {}
+        }
+
+        let (operation, key_value) = split_once_or_err(leftover, " ")?;
+        let key = key_value.to_owned();
+        match operation {
+            "set" => {
+                let (key, value) = split_once_or_err(key_value, " ")?;
+                Ok(HmapOpType::Set {
+                    target,
+                    key: key.to_owned(),
+                    value: value.to_owned(),
+                })
+            }
+            "get" => Ok(HmapOpType::Get { target, key }),
+            "del" => Ok(HmapOpType::Del { target, key }),
+            "exists" => Ok(HmapOpType::Exists { target, key }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl HmapOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing hmap request: {:?}", self);
+        match self {
+            // hmap <target> set <key> <value>
+            HmapOpType::Set { target, key, value } => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |hmap: &mut HashMap| {
+                        hmap.set(key, value);
+                        format!("Key {} set", key)
+                    })
+                    .await
+            }
+            // hmap <target> get <key>
+            HmapOpType::Get { target, key } => {
+                let platforn_lock = platform.read().await;
+                let value = platforn_lock
+                    .with_ref(target, |hmap: &HashMap| hmap.get(key).cloned())
+                    .await?;
+                value.ok_or_else(|| "Key not found".to_string())
+            }
+            // hmap <target> del <key>
+            HmapOpType::Del { target, key } => {
+                let platforn_lock = platform.write().await;
+                let removed = platforn_lock
+                    .with(target, |hmap: &mut HashMap| hmap.del(key))
+                    .await?;
+                if !removed {
+                    return Err("Key not found".to_string());
+                }
+                Ok(format!("Key {} removed", key))
+            }
+            // hmap <target> exists <key>
+            HmapOpType::Exists { target, key } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |hmap: &HashMap| hmap.exists(key).to_string())
+                    .await
+            }
+            // hmap <target> len
+            HmapOpType::Len { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |hmap: &HashMap| hmap.len().to_string())
+                    .await
+            }
+            // hmap <target> keys
+            HmapOpType::Keys { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |hmap: &HashMap| format!("{:?}", hmap.keys()))
+                    .await
+            }
+            // hmap <target> scan
+            HmapOpType::Scan { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |hmap: &HashMap| format!("{:?}", hmap.scan()))
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_hmap() {
+        match HmapOpType::try_from("sessions set abc123 user 1".to_string()).unwrap() {
+            HmapOpType::Set { target, key, value } => {
+                assert_eq!(target, "sessions".to_string());
+                assert_eq!(key, "abc123".to_string());
+                assert_eq!(value, "user 1".to_string());
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(matches!(
+            HmapOpType::try_from("sessions len".to_string()),
+            Ok(HmapOpType::Len { .. })
+        ));
+        assert!(HmapOpType::try_from("sessions set abc123".to_string()).is_err());
+        assert!(HmapOpType::try_from("sessions pop abc123".to_string()).is_err());
+    }
+}
@@ -0,0 +1,313 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::dsa::list::List;
+use log::debug;
+use tokio::sync::RwLock;
+use tokio::time::{self, Instant};
+
+use crate::platform::Platform;
+
+use super::{split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum ListOpType {
+    Push {
+        target: String,
+        value: String,
+        front: bool,
+    },
+    Pop {
+        target: String,
+        front: bool,
+    },
+    /// Waits until the list has a value. A zero timeout waits forever.
+    BlockingPop {
+        target: String,
+        front: bool,
+        timeout: Duration,
+    },
+    Range {
+        target: String,
+        start: i64,
+        stop: i64,
+    },
+    Len {
+        target: String,
+    },
+    Trim {
+        target: String,
+        start: i64,
+        stop: i64,
+    },
+}
+
+fn parse_range(args: &str) -> Result<(i64, i64), RequestParserError> {
+    let (start, stop) = split_once_or_err(args, " ")?;
+    match (start.parse(), stop.parse()) {
+        (Ok(start), Ok(stop)) => Ok((start, stop)),
+        _ => Err(RequestParserError::InvalidRequest),
+    }
+}
+
+impl TryFrom<String> for ListOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("ListOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        match leftover {
+            "lpop" | "rpop" => {
+                return Ok(ListOpType::Pop {
+                    target,
+                    front: leftover == "lpop",
+                })
+            }
+            "llen" => return Ok(ListOpType::Len { target }),
+            _ => {}
+        }
+
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        match operation {
+            "lpush" | "rpush" => Ok(ListOpType::Push {
+                target,
+                value: args.to_owned(),
+                front: operation == "lpush",
+            }),
+            "blpop" | "brpop" => {
+                let timeout = args
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                    .ok_or(RequestParserError::InvalidRequest)?;
+                Ok(ListOpType::BlockingPop {
+                    target,
+                    front: operation == "blpop",
+                    timeout,
+                })
+            }
+            "lrange" => {
+                let (start, stop) = parse_range(args)?;
+                Ok(ListOpType::Range {
+                    target,
+                    start,
+                    stop,
+                })
+            }
+            "ltrim" => {
+                let (start, stop) = parse_range(args)?;
+                Ok(ListOpType::Trim {
+                    target,
+                    start,
+                    stop,
+                })
+            }
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl ListOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing list request: {:?}", self);
+        match self {
+            // list <target> lpush|rpush <value>
+            ListOpType::Push {
+                target,
+                value,
+                front,
+            } => {
+                let platforn_lock = platform.write().await;
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                let len = data_structures_lock
+                    .with(target, |list: &mut List| {
+                        if *front {
+                            list.push_front(value)
+                        } else {
+                            list.push_back(value)
+                        }
+                    })
+                    .await?;
+                data_structures_lock.notify_list_pushes(target).await;
+                Ok(len.to_string())
+            }
+            // list <target> lpop|rpop
+            ListOpType::Pop { target, front } => {
+                let platforn_lock = platform.write().await;
+                let value = platforn_lock
+                    .with(target, |list: &mut List| pop(list, *front))
+                    .await?;
+                value.ok_or_else(|| "List is empty".to_string())
+            }
+            // list <target> blpop|brpop <timeout seconds>
+            ListOpType::BlockingPop {
+                target,
+                front,
+                timeout,
+            } => {
+                // Timeouts too far away to represent wait forever, like a zero timeout.
+                let deadline = (!timeout.is_zero())
+                    .then(|| Instant::now().checked_add(*timeout))
+                    .flatten();
+                loop {
+                    let notify;
+                    let pushed = {
+                        let platforn_lock = platform.write().await;
+                        let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                        let value = data_structures_lock
+                            .with(target, |list: &mut List| pop(list, *front))
+                            .await?;
+                        if let Some(value) = value {
+                            return Ok(value);
+                        }
+                        // The list exists and is empty. Registered before the locks are released,
+                        // so a push right after can't be missed.
+                        notify = data_structures_lock.list_pushes(target).await;
+                        notify.notified()
+                    };
+                    // Wait without holding any lock, pushes need the platform write lock.
+                    match deadline {
+                        Some(deadline) => {
+                            if time::timeout_at(deadline, pushed).await.is_err() {
+                                return Err("Timed out".to_string());
+                            }
+                        }
+                        None => pushed.await,
+                    }
+                }
+            }
+            // list <target> lrange <start> <stop>
+            ListOpType::Range {
+                target,
+                start,
+                stop,
+            } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |list: &List| {
+                        format!("{:?}", list.range(*start, *stop))
+                    })
+                    .await
+            }
+            // list <target> llen
+            ListOpType::Len { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |list: &List| list.len().to_string())
+                    .await
+            }
+            // list <target> ltrim <start> <stop>
+            ListOpType::Trim {
+                target,
+                start,
+                stop,
+            } => {
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |list: &mut List| list.trim(*start, *stop))
+                    .await?;
+                Ok(format!("List {} trimmed", target))
+            }
+        }
+    }
+}
+
+/// Pops a value from the front or the back of the list.
+fn pop(list: &mut List, front: bool) -> Option<String> {
+    if front {
+        list.pop_front()
+    } else {
+        list.pop_back()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_list() {
+        match ListOpType::try_from("jobs lpush send email".to_string()).unwrap() {
+            ListOpType::Push {
+                target,
+                value,
+                front,
+            } => {
+                assert_eq!(target, "jobs".to_string());
+                assert_eq!(value, "send email".to_string());
+                assert!(front);
+            }
+            _ => panic!("unexpected result"),
+        }
+        match ListOpType::try_from("jobs brpop 0.5".to_string()).unwrap() {
+            ListOpType::BlockingPop { front, timeout, .. } => {
+                assert!(!front);
+                assert_eq!(timeout, Duration::from_millis(500));
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(ListOpType::try_from("jobs blpop -1".to_string()).is_err());
+        assert!(ListOpType::try_from("jobs ltrim 0".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pop() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        run(&platform, "create list jobs").await.unwrap();
+        assert_eq!(
+            run(&platform, "list jobs blpop 0.05").await,
+            Err("Timed out".to_string())
+        );
+
+        let waiter = {
+            let platform = Arc::clone(&platform);
+            tokio::spawn(async move { run(&platform, "list jobs brpop 0").await })
+        };
+        time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(run(&platform, "list jobs lpush job1").await.unwrap(), "1");
+        assert_eq!(waiter.await.unwrap(), Ok("job1".to_string()));
+        assert_eq!(run(&platform, "list jobs llen").await.unwrap(), "0");
+
+        let waiter = {
+            let platform = Arc::clone(&platform);
+            tokio::spawn(async move { run(&platform, "list jobs blpop 0").await })
+        };
+        time::sleep(Duration::from_millis(20)).await;
+        run(&platform, "destroy list jobs").await.unwrap();
+        assert_eq!(waiter.await.unwrap(), Err("List not found".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pop_with_huge_timeout() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        run(&platform, "create list jobs").await.unwrap();
+        let waiter = {
+            let platform = Arc::clone(&platform);
+            tokio::spawn(async move { run(&platform, "list jobs blpop 1e19").await })
+        };
+        time::sleep(Duration::from_millis(20)).await;
+        run(&platform, "list jobs rpush job1").await.unwrap();
+        assert_eq!(waiter.await.unwrap(), Ok("job1".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_blocking_pop_on_missing_list() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        assert_eq!(
+            run(&platform, "list missing blpop 0").await,
+            Err("List not found".to_string())
+        );
+        let platforn_lock = platform.read().await;
+        let data_structures_lock = platforn_lock.r_lock_data_structures().await;
+        assert!(!data_structures_lock.has_list_pushes("missing").await);
+    }
+}
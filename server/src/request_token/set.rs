@@ -0,0 +1,228 @@
+use std::sync::Arc;
+
+use common::dsa::set::{self, Set};
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{split_once_or_err, RequestParserError};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SetAlgebra {
+    Union,
+    Intersection,
+    Difference,
+}
+
+#[derive(Debug)]
+pub enum SetOpType {
+    Add {
+        target: String,
+        member: String,
+    },
+    Remove {
+        target: String,
+        member: String,
+    },
+    IsMember {
+        target: String,
+        member: String,
+    },
+    Members {
+        target: String,
+    },
+    Card {
+        target: String,
+    },
+    /// Combines the sources, storing the result as a new set if a destination is given.
+    Algebra {
+        algebra: SetAlgebra,
+        sources: Vec<String>,
+        destination: Option<String>,
+    },
+}
+
+impl SetOpType {
+    /// Parses the commands working on several sets: sunion, sinter, sdiff and their store variants.
+    pub fn from_algebra(command: &str, args: &str) -> Result<Self, RequestParserError> {
+        let (algebra, store) = match command {
+            "sunion" => (SetAlgebra::Union, false),
+            "sinter" => (SetAlgebra::Intersection, false),
+            "sdiff" => (SetAlgebra::Difference, false),
+            "sunionstore" => (SetAlgebra::Union, true),
+            "sinterstore" => (SetAlgebra::Intersection, true),
+            "sdiffstore" => (SetAlgebra::Difference, true),
+            _ => return Err(RequestParserError::InvalidRequest),
+        };
+        let mut names = args.split_whitespace().map(str::to_owned);
+        let destination = if store { names.next() } else { None };
+        let sources: Vec<String> = names.collect();
+        if sources.is_empty() {
+            return Err(RequestParserError::InvalidRequest);
+        }
+        Ok(SetOpType::Algebra {
+            algebra,
+            sources,
+            destination,
+        })
+    }
+}
+
+impl TryFrom<String> for SetOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("SetOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        match leftover {
+            "smembers" => return Ok(SetOpType::Members { target }),
+            "scard" => return Ok(SetOpType::Card { target }),
+            _ => {}
+        }
+
+        let (operation, member) = split_once_or_err(leftover, " ")?;
+        let member = member.to_owned();
+        match operation {
+            "sadd" => Ok(SetOpType::Add { target, member }),
+            "srem" => Ok(SetOpType::Remove { target, member }),
+            "sismember" => Ok(SetOpType::IsMember { target, member }),
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl SetOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing set request: {:?}", self);
+        match self {
+            // set <target> sadd <member>
+            SetOpType::Add { target, member } => {
+                let platforn_lock = platform.write().await;
+                let added = platforn_lock
+                    .with(target, |set: &mut Set| set.add(member))
+                    .await?;
+                if !added {
+                    return Ok(format!("Member {} already present", member));
+                }
+                Ok(format!("Member {} added", member))
+            }
+            // set <target> srem <member>
+            SetOpType::Remove { target, member } => {
+                let platforn_lock = platform.write().await;
+                let removed = platforn_lock
+                    .with(target, |set: &mut Set| set.remove(member))
+                    .await?;
+                if !removed {
+                    return Err("Member not found".to_string());
+                }
+                Ok(format!("Member {} removed", member))
+            }
+            // set <target> sismember <member>
+            SetOpType::IsMember { target, member } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |set: &Set| set.contains(member).to_string())
+                    .await
+            }
+            // set <target> smembers
+            SetOpType::Members { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |set: &Set| format!("{:?}", set.members()))
+                    .await
+            }
+            // set <target> scard
+            SetOpType::Card { target } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |set: &Set| set.len().to_string())
+                    .await
+            }
+            // sunion|sinter|sdiff <set> [<set> ...]
+            // sunionstore|sinterstore|sdiffstore <destination> <set> [<set> ...]
+            SetOpType::Algebra {
+                algebra,
+                sources,
+                destination,
+            } => {
+                let platforn_lock = platform.write().await;
+                let data_structures_lock = platforn_lock.rw_lock_data_structures().await;
+                let result = {
+                    let guards = data_structures_lock.read_many::<Set>(sources).await?;
+                    let sets: Vec<&Set> = sources.iter().map(|name| &*guards[name]).collect();
+                    let name = destination.clone().unwrap_or_default();
+                    match algebra {
+                        SetAlgebra::Union => set::union(name, &sets),
+                        SetAlgebra::Intersection => set::intersection(name, &sets),
+                        SetAlgebra::Difference => set::difference(name, &sets),
+                    }
+                };
+                if destination.is_none() {
+                    return Ok(format!("{:?}", result.members()));
+                }
+                let len = result.len();
+                // The sources are unlocked here, so the destination may be one of them.
+                data_structures_lock.replace(result).await;
+                Ok(len.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_algebra() {
+        match SetOpType::from_algebra("sdiffstore", "result first second").unwrap() {
+            SetOpType::Algebra {
+                algebra,
+                sources,
+                destination,
+            } => {
+                assert_eq!(algebra, SetAlgebra::Difference);
+                assert_eq!(sources, vec!["first".to_string(), "second".to_string()]);
+                assert_eq!(destination, Some("result".to_string()));
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(SetOpType::from_algebra("sunionstore", "result").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_algebra() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create set a",
+            "create set b",
+            "set a sadd 1",
+            "set a sadd 2",
+            "set b sadd 2",
+            "set b sadd 3",
+        ] {
+            run(&platform, request).await.unwrap();
+        }
+        assert_eq!(
+            run(&platform, "sunion a b").await.unwrap(),
+            r#"["1", "2", "3"]"#
+        );
+        assert_eq!(run(&platform, "sinter b a b").await.unwrap(), r#"["2"]"#);
+        assert_eq!(
+            run(&platform, "sdiff a missing").await,
+            Err("Set not found".to_string())
+        );
+        assert_eq!(run(&platform, "sdiffstore a a b").await.unwrap(), "1");
+        assert_eq!(run(&platform, "set a smembers").await.unwrap(), r#"["1"]"#);
+    }
+}
@@ -0,0 +1,224 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::dsa::time_series::{Aggregation, TimeSeries};
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{parse, split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum TsOpType {
+    Add {
+        target: String,
+        /// Milliseconds since the UNIX epoch, the current time if None.
+        timestamp: Option<u64>,
+        value: f64,
+    },
+    Range {
+        target: String,
+        from: u64,
+        to: u64,
+        /// Aggregation and bucket size in milliseconds.
+        aggregation: Option<(Aggregation, u64)>,
+    },
+}
+
+impl TryFrom<String> for TsOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("TsOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        let args: Vec<&str> = args.split(' ').collect();
+        match (operation, args.as_slice()) {
+            ("tsadd", [timestamp, value]) => Ok(TsOpType::Add {
+                target,
+                timestamp: match *timestamp {
+                    "*" => None,
+                    timestamp => Some(parse(timestamp)?),
+                },
+                value: parse(value)?,
+            }),
+            ("tsrange", [from, to]) => Ok(TsOpType::Range {
+                target,
+                from: parse(from)?,
+                to: parse(to)?,
+                aggregation: None,
+            }),
+            ("tsrange", [from, to, aggregation, bucket]) => {
+                let aggregation = match *aggregation {
+                    "avg" => Aggregation::Avg,
+                    "min" => Aggregation::Min,
+                    "max" => Aggregation::Max,
+                    "sum" => Aggregation::Sum,
+                    "count" => Aggregation::Count,
+                    _ => return Err(RequestParserError::InvalidRequest),
+                };
+                let bucket = parse(bucket)?;
+                if bucket == 0 {
+                    return Err(RequestParserError::InvalidRequest);
+                }
+                Ok(TsOpType::Range {
+                    target,
+                    from: parse(from)?,
+                    to: parse(to)?,
+                    aggregation: Some((aggregation, bucket)),
+                })
+            }
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl TsOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing ts request: {:?}", self);
+        match self {
+            // ts <target> tsadd <timestamp|*> <value>
+            TsOpType::Add {
+                target,
+                timestamp,
+                value,
+            } => {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|duration| duration.as_millis() as u64)
+                    .unwrap_or(0);
+                let platforn_lock = platform.write().await;
+                platforn_lock
+                    .with(target, |series: &mut TimeSeries| {
+                        // Samples added with * in the same millisecond go one millisecond apart.
+                        let timestamp =
+                            timestamp.unwrap_or_else(|| match series.last_timestamp() {
+                                Some(last) => now.max(last.saturating_add(1)),
+                                None => now,
+                            });
+                        series.add(timestamp, *value).map_err(|e| e.to_string())?;
+                        Ok(timestamp.to_string())
+                    })
+                    .await?
+            }
+            // ts <target> tsrange <from> <to> [<avg|min|max|sum|count> <bucket>]
+            TsOpType::Range {
+                target,
+                from,
+                to,
+                aggregation,
+            } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |series: &TimeSeries| {
+                        let samples = match aggregation {
+                            Some((aggregation, bucket)) => {
+                                series.aggregate(*from, *to, *aggregation, *bucket)
+                            }
+                            None => series.range(*from, *to),
+                        };
+                        format!("{:?}", samples)
+                    })
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request_token::RequestToken;
+
+    async fn run(platform: &Arc<RwLock<Platform>>, request: &str) -> Result<String, String> {
+        RequestToken::try_from(request.to_string())
+            .unwrap()
+            .execute(platform)
+            .await
+    }
+
+    #[test]
+    fn test_tokenize_ts() {
+        match TsOpType::try_from("latency tsrange 0 60000 max 1000".to_string()).unwrap() {
+            TsOpType::Range {
+                target,
+                from,
+                to,
+                aggregation,
+            } => {
+                assert_eq!(target, "latency".to_string());
+                assert_eq!((from, to), (0, 60000));
+                assert_eq!(aggregation, Some((Aggregation::Max, 1000)));
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(matches!(
+            TsOpType::try_from("latency tsadd * 1.5".to_string()),
+            Ok(TsOpType::Add {
+                timestamp: None,
+                ..
+            })
+        ));
+        assert!(TsOpType::try_from("latency tsrange 0 10 max 0".to_string()).is_err());
+        assert!(TsOpType::try_from("latency tsrange 0 10 median 5".to_string()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_add_and_range() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        for request in [
+            "create ts latency 2000",
+            "ts latency tsadd 1000 5",
+            "ts latency tsadd 1500 7",
+            "ts latency tsadd 2000 1.5",
+        ] {
+            run(&platform, request).await.unwrap();
+        }
+        assert_eq!(
+            run(&platform, "ts latency tsrange 0 1500").await.unwrap(),
+            "[(1000, 5.0), (1500, 7.0)]"
+        );
+        assert_eq!(
+            run(&platform, "ts latency tsrange 0 5000 avg 1000")
+                .await
+                .unwrap(),
+            "[(1000, 6.0), (2000, 1.5)]"
+        );
+        assert_eq!(
+            run(&platform, "ts latency tsadd 1500 1").await,
+            Err("timestamp must be after the last sample at 2000".to_string())
+        );
+        // Past the retention of 2 seconds before the last sample.
+        run(&platform, "ts latency tsadd 3200 0").await.unwrap();
+        assert_eq!(
+            run(&platform, "ts latency tsrange 0 5000 count 10000")
+                .await
+                .unwrap(),
+            "[(0, 3.0)]"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_add_current_time() {
+        let platform = Arc::new(RwLock::new(Platform::new()));
+        run(&platform, "create ts latency").await.unwrap();
+        let mut timestamps = Vec::new();
+        for _ in 0..3 {
+            let timestamp = run(&platform, "ts latency tsadd * 1").await.unwrap();
+            timestamps.push(timestamp.parse::<u64>().unwrap());
+        }
+        assert!(timestamps.windows(2).all(|pair| pair[0] < pair[1]));
+
+        // A later sample than the current time pushes the next ones after it.
+        let future = timestamps[2] + 60_000;
+        run(&platform, &format!("ts latency tsadd {} 1", future))
+            .await
+            .unwrap();
+        assert_eq!(
+            run(&platform, "ts latency tsadd * 1").await.unwrap(),
+            (future + 1).to_string()
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use std::sync::Arc;
+
+use common::dsa::sorted_set::SortedSet;
+use log::debug;
+use tokio::sync::RwLock;
+
+use crate::platform::Platform;
+
+use super::{parse, split_once_or_err, RequestParserError};
+
+#[derive(Debug)]
+pub enum ZsetOpType {
+    Add {
+        target: String,
+        score: f64,
+        member: String,
+    },
+    Remove {
+        target: String,
+        member: String,
+    },
+    Score {
+        target: String,
+        member: String,
+    },
+    Rank {
+        target: String,
+        member: String,
+    },
+    Range {
+        target: String,
+        start: i64,
+        stop: i64,
+        reverse: bool,
+    },
+    RangeByScore {
+        target: String,
+        min: f64,
+        max: f64,
+    },
+    IncrBy {
+        target: String,
+        delta: f64,
+        member: String,
+    },
+}
+
+/// Parses a score, rejecting NaN.
+fn parse_score(value: &str) -> Result<f64, RequestParserError> {
+    let score: f64 = parse(value)?;
+    if score.is_nan() {
+        return Err(RequestParserError::InvalidRequest);
+    }
+    Ok(score)
+}
+
+impl TryFrom<String> for ZsetOpType {
+    type Error = RequestParserError;
+
+    fn try_from(value: String) -> Result<Self, RequestParserError> {
+        debug!("ZsetOpType from string: {}", &value);
+        let (target, leftover) = split_once_or_err(&value, " ")?;
+        let target = target.to_owned();
+        let (operation, args) = split_once_or_err(leftover, " ")?;
+        let member = args.to_owned();
+        match operation {
+            "zadd" => {
+                let (score, member) = split_once_or_err(args, " ")?;
+                Ok(ZsetOpType::Add {
+                    target,
+                    score: parse_score(score)?,
+                    member: member.to_owned(),
+                })
+            }
+            "zrem" => Ok(ZsetOpType::Remove { target, member }),
+            "zscore" => Ok(ZsetOpType::Score { target, member }),
+            "zrank" => Ok(ZsetOpType::Rank { target, member }),
+            "zrange" | "zrevrange" => {
+                let (start, stop) = split_once_or_err(args, " ")?;
+                Ok(ZsetOpType::Range {
+                    target,
+                    start: parse(start)?,
+                    stop: parse(stop)?,
+                    reverse: operation == "zrevrange",
+                })
+            }
+            "zrangebyscore" => {
+                let (min, max) = split_once_or_err(args, " ")?;
+                Ok(ZsetOpType::RangeByScore {
+                    target,
+                    min: parse_score(min)?,
+                    max: parse_score(max)?,
+                })
+            }
+            "zincrby" => {
+                let (delta, member) = split_once_or_err(args, " ")?;
+                Ok(ZsetOpType::IncrBy {
+                    target,
+                    delta: parse_score(delta)?,
+                    member: member.to_owned(),
+                })
+            }
+            _ => Err(RequestParserError::InvalidRequest),
+        }
+    }
+}
+
+impl ZsetOpType {
+    pub async fn execute(&self, platform: &Arc<RwLock<Platform>>) -> Result<String, String> {
+        debug!("Executing zset request: {:?}", self);
+        match self {
+            // zset <target> zadd <score> <member>
+            ZsetOpType::Add {
+                target,
+                score,
+                member,
+            } => {
+                let platforn_lock = platform.write().await;
+                let added = platforn_lock
+                    .with(target, |zset: &mut SortedSet| zset.add(member, *score))
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                if added {
+                    Ok(format!("Member {} added", member))
+                } else {
+                    Ok(format!("Member {} updated", member))
+                }
+            }
+            // zset <target> zrem <member>
+            ZsetOpType::Remove { target, member } => {
+                let platforn_lock = platform.write().await;
+                let removed = platforn_lock
+                    .with(target, |zset: &mut SortedSet| zset.remove(member))
+                    .await?;
+                if !removed {
+                    return Err("Member not found".to_string());
+                }
+                Ok(format!("Member {} removed", member))
+            }
+            // zset <target> zscore <member>
+            ZsetOpType::Score { target, member } => {
+                let platforn_lock = platform.read().await;
+                let score = platforn_lock
+                    .with_ref(target, |zset: &SortedSet| zset.score(member))
+                    .await?;
+                match score {
+                    Some(score) => Ok(score.to_string()),
+                    None => Err("Member not found".to_string()),
+                }
+            }
+            // zset <target> zrank <member>
+            ZsetOpType::Rank { target, member } => {
+                let platforn_lock = platform.read().await;
+                let rank = platforn_lock
+                    .with_ref(target, |zset: &SortedSet| zset.rank(member))
+                    .await?;
+                match rank {
+                    Some(rank) => Ok(rank.to_string()),
+                    None => Err("Member not found".to_string()),
+                }
+            }
+            // zset <target> zrange|zrevrange <start> <stop>
+            ZsetOpType::Range {
+                target,
+                start,
+                stop,
+                reverse,
+            } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |zset: &SortedSet| {
+                        let members = if *reverse {
+                            zset.rev_range(*start, *stop)
+                        } else {
+                            zset.range(*start, *stop)
+                        };
+                        format!("{:?}", members)
+                    })
+                    .await
+            }
+            // zset <target> zrangebyscore <min> <max>
+            ZsetOpType::RangeByScore { target, min, max } => {
+                let platforn_lock = platform.read().await;
+                platforn_lock
+                    .with_ref(target, |zset: &SortedSet| {
+                        format!("{:?}", zset.range_by_score(*min, *max))
+                    })
+                    .await
+            }
+            // zset <target> zincrby <delta> <member>
+            ZsetOpType::IncrBy {
+                target,
+                delta,
+                member,
+            } => {
+                let platforn_lock = platform.write().await;
+                let score = platforn_lock
+                    .with(target, |zset: &mut SortedSet| zset.incr_by(member, *delta))
+                    .await?
+                    .map_err(|e| e.to_string())?;
+                Ok(score.to_string())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_zset() {
+        match ZsetOpType::try_from("board zadd 12.5 player one".to_string()).unwrap() {
+            ZsetOpType::Add {
+                target,
+                score,
+                member,
+            } => {
+                assert_eq!(target, "board".to_string());
+                assert_eq!(score, 12.5);
+                assert_eq!(member, "player one".to_string());
+            }
+            _ => panic!("unexpected result"),
+        }
+        match ZsetOpType::try_from("board zrangebyscore -inf 10".to_string()).unwrap() {
+            ZsetOpType::RangeByScore { min, max, .. } => {
+                assert_eq!(min, f64::NEG_INFINITY);
+                assert_eq!(max, 10.0);
+            }
+            _ => panic!("unexpected result"),
+        }
+        assert!(matches!(
+            ZsetOpType::try_from("board zrevrange 0 -1".to_string()),
+            Ok(ZsetOpType::Range { reverse: true, .. })
+        ));
+        assert!(ZsetOpType::try_from("board zadd NaN player".to_string()).is_err());
+        assert!(ZsetOpType::try_from("board zrange 0".to_string()).is_err());
+    }
+}